@@ -103,6 +103,36 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
     assert!(r.is_ok());
 
+    // Check cancellation using atomic trigger in a thread observed by a liveness guard
+    // (the liveness overhead only applies to observed threads).
+    #[cfg(feature = "liveness")]
+    {
+        let guard = cancel_this::LivenessGuard::new(Duration::from_secs(600), |_| {});
+        let r: Cancellable<()> = cancel_this::on_atomic(CancelAtomic::default(), || {
+            c.bench_function(
+                format!("{bench_prefix}::cancellable::atomic::observed; {bench_key}").as_str(),
+                |b| b.iter(|| cancellable_hash_data(black_box(&data))),
+            );
+            Ok(())
+        });
+        assert!(r.is_ok());
+        drop(guard);
+    }
+
+    // Check cancellation using several nested atomic triggers.
+    let r: Cancellable<()> = cancel_this::on_atomic(CancelAtomic::default(), || {
+        cancel_this::on_atomic(CancelAtomic::default(), || {
+            cancel_this::on_atomic(CancelAtomic::default(), || {
+                c.bench_function(
+                    format!("{bench_prefix}::cancellable::atomic::nested; {bench_key}").as_str(),
+                    |b| b.iter(|| cancellable_hash_data(black_box(&data))),
+                );
+                Ok(())
+            })
+        })
+    });
+    assert!(r.is_ok());
+
     /*
        Fundamentally, these should not be any slower,
        because internally they use atomic triggers.
@@ -0,0 +1,44 @@
+use std::fmt::{Display, Formatter};
+
+/// A location in the source code where cancellation is checked, recorded by
+/// [`crate::is_cancelled`] (with feature `liveness` enabled) to help locate
+/// unresponsive code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallSite {
+    file: &'static str,
+    line: u32,
+    module_path: &'static str,
+}
+
+impl CallSite {
+    /// Create a new [`CallSite`]. In typical situations, you don't use this method directly,
+    /// but the call site is created by the [`crate::is_cancelled`] macro.
+    pub const fn new(file: &'static str, line: u32, module_path: &'static str) -> Self {
+        CallSite {
+            file,
+            line,
+            module_path,
+        }
+    }
+
+    /// The source file of the call site (see [`file!`]).
+    pub fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// The line of the call site (see [`line!`]).
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The module path of the call site (see [`module_path!`]).
+    pub fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+}
+
+impl Display for CallSite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
@@ -9,6 +9,7 @@ use std::fmt::{Debug, Display, Formatter};
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Cancelled {
     cause: &'static str,
+    detail: Option<String>,
 }
 
 /// A result of a cancellable operation.
@@ -17,13 +18,37 @@ pub type Cancellable<TResult> = Result<TResult, Cancelled>;
 impl Cancelled {
     /// Create a new [`Cancelled`] with a cause type.
     pub fn new(cause: &'static str) -> Self {
-        Cancelled { cause }
+        Cancelled {
+            cause,
+            detail: None,
+        }
+    }
+
+    /// Create a new [`Cancelled`] with a cause type and a human-readable `detail`
+    /// (e.g., the value observed by the trigger when it was canceled).
+    pub fn with_detail<T: Into<String>>(cause: &'static str, detail: T) -> Self {
+        Cancelled {
+            cause,
+            detail: Some(detail.into()),
+        }
+    }
+
+    /// Create a new [`Cancelled`] with a cause type and an optional `detail`.
+    pub(crate) fn with_optional_detail(cause: &'static str, detail: Option<String>) -> Self {
+        Cancelled { cause, detail }
     }
 }
 
 impl Display for Cancelled {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Operation cancelled (caused by `{}`)", self.cause)
+        match &self.detail {
+            None => write!(f, "Operation cancelled (caused by `{}`)", self.cause),
+            Some(detail) => write!(
+                f,
+                "Operation cancelled (caused by `{}`: {})",
+                self.cause, detail
+            ),
+        }
     }
 }
 
@@ -41,6 +66,12 @@ impl Cancelled {
     pub fn cause(&self) -> &'static str {
         self.cause
     }
+
+    /// Additional information about the cancellation provided by the trigger
+    /// (see [`crate::CancellationTrigger::detail`]), if any.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
 }
 
 #[cfg(test)]
@@ -61,5 +92,12 @@ mod tests {
             default.to_string(),
             "Operation cancelled (caused by `UnknownCancellationTrigger`)"
         );
+        assert_eq!(default.detail(), None);
+        let detailed = Cancelled::with_detail("CancelMemory", "observed 120 (limit: > 100)");
+        assert_eq!(detailed.detail(), Some("observed 120 (limit: > 100)"));
+        assert_eq!(
+            detailed.to_string(),
+            "Operation cancelled (caused by `CancelMemory`: observed 120 (limit: > 100))"
+        );
     }
 }
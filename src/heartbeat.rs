@@ -0,0 +1,159 @@
+use std::fs::OpenOptions;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::time::{Instant, SystemTime};
+
+/// An external heartbeat driven by a [`crate::LivenessGuard`]
+/// (see [`crate::LivenessGuardBuilder::heartbeat`]).
+///
+/// The heartbeat is sent once per window as long as none of the observed threads is
+/// [`crate::LivenessState::Stalled`]. Once a thread stalls, the heartbeat stops, such that
+/// the external supervisor (e.g., systemd or Kubernetes) can restart the process.
+///
+/// ```rust
+/// # use std::sync::mpsc::channel;
+/// # use std::time::Duration;
+/// # use cancel_this::{HeartbeatSink, LivenessGuard};
+/// let (sender, receiver) = channel();
+/// let guard = LivenessGuard::builder()
+///     .threshold(Duration::from_millis(20))
+///     .heartbeat(HeartbeatSink::Channel(sender))
+///     .build();
+///
+/// // The thread does not check cancellation, so the heartbeat eventually stops.
+/// std::thread::sleep(Duration::from_millis(200));
+/// let beats = receiver.try_iter().count();
+/// assert!(beats >= 1 && beats < 5);
+/// drop(guard);
+/// ```
+#[derive(Debug, Clone)]
+pub enum HeartbeatSink {
+    /// Send `WATCHDOG=1` to a systemd notification socket (a Unix datagram socket). Paths
+    /// starting with `@` refer to the Linux abstract namespace.
+    ///
+    /// See also [`HeartbeatSink::systemd`].
+    #[cfg(unix)]
+    NotifySocket(PathBuf),
+    /// Update the modification time of a file (the file is created if it does not exist).
+    File(PathBuf),
+    /// Send the time of the heartbeat to a channel.
+    Channel(Sender<Instant>),
+}
+
+impl HeartbeatSink {
+    /// The systemd watchdog of the current service, as given by the `NOTIFY_SOCKET`
+    /// environment variable (if set). Note that the window of the guard should be
+    /// considerably shorter than `WatchdogSec`.
+    #[cfg(unix)]
+    pub fn systemd() -> Option<HeartbeatSink> {
+        let socket = std::env::var_os("NOTIFY_SOCKET")?;
+        Some(HeartbeatSink::NotifySocket(PathBuf::from(socket)))
+    }
+
+    /// Send a single heartbeat. The heartbeat is sent by the callback thread of the guard,
+    /// hence it must never block, since that would delay the callbacks of the guard
+    /// (a heartbeat that cannot be sent immediately is missed).
+    pub(crate) fn beat(&self) -> Result<(), Error> {
+        match self {
+            #[cfg(unix)]
+            HeartbeatSink::NotifySocket(path) => {
+                let socket = std::os::unix::net::UnixDatagram::unbound()?;
+                socket.set_nonblocking(true)?;
+                match socket.send_to_addr(b"WATCHDOG=1", &notify_address(path)?) {
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => Err(Error::new(
+                        ErrorKind::WouldBlock,
+                        "Notification socket is full; heartbeat missed.",
+                    )),
+                    result => result.map(|_| ()),
+                }
+            }
+            HeartbeatSink::File(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                file.set_modified(SystemTime::now())
+            }
+            HeartbeatSink::Channel(sender) => sender
+                .send(Instant::now())
+                .map_err(|_| Error::new(ErrorKind::BrokenPipe, "Heartbeat receiver is closed.")),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn notify_address(path: &std::path::Path) -> Result<std::os::unix::net::SocketAddr, Error> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+        if let Some(name) = path.as_os_str().as_bytes().strip_prefix(b"@") {
+            return std::os::unix::net::SocketAddr::from_abstract_name(name);
+        }
+    }
+    std::os::unix::net::SocketAddr::from_pathname(path)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::{HeartbeatSink, LivenessGuard};
+    use std::os::unix::net::UnixDatagram;
+    use std::time::Duration;
+
+    #[test]
+    fn heartbeat_sinks() {
+        let dir =
+            std::env::temp_dir().join(format!("cancel-this-heartbeat-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket_path = dir.join("notify.sock");
+        let file_path = dir.join("heartbeat");
+        let _ = std::fs::remove_file(&socket_path);
+        let _ = std::fs::remove_file(&file_path);
+        let socket = UnixDatagram::bind(&socket_path).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let guard = LivenessGuard::builder()
+            .threshold(Duration::from_millis(20))
+            // The file is touched before the socket receives the heartbeat.
+            .heartbeat(HeartbeatSink::File(file_path.clone()))
+            .heartbeat(HeartbeatSink::NotifySocket(socket_path.clone()))
+            .build();
+        let mut buffer = [0u8; 64];
+        let length = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], b"WATCHDOG=1");
+        assert!(file_path.exists());
+
+        // The thread does not check cancellation, so it stalls and the heartbeat stops.
+        std::thread::sleep(Duration::from_millis(150));
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        while socket.recv(&mut buffer).is_ok() {}
+        let modified = std::fs::metadata(&file_path).unwrap().modified().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().modified().unwrap(),
+            modified
+        );
+        drop(guard);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn heartbeat_full_socket() {
+        let dir =
+            std::env::temp_dir().join(format!("cancel-this-heartbeat-full-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket_path = dir.join("notify.sock");
+        let _ = std::fs::remove_file(&socket_path);
+        // Nobody reads the socket, so its receive queue eventually fills up.
+        let _socket = UnixDatagram::bind(&socket_path).unwrap();
+        let sink = HeartbeatSink::NotifySocket(socket_path);
+        let error = std::iter::repeat_with(|| sink.beat())
+            .take(100_000)
+            .find_map(|it| it.err())
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -23,12 +23,36 @@
 //!
 //! - Scoped cancellation using thread-local "cancellation triggers".
 //! - Out-of-the-box support for triggers based on atomics and timers.
+//! - Interrupting a specific thread by its `ThreadId` (see [`interrupt`]).
+//! - Process-global triggers consulted by every thread (see [`install_global`] and [`shutdown`]).
+//! - Generic triggers based on sampled metrics (e.g., open files or queue depth) with
+//!   configurable sampling policy.
 //! - With feature `ctrlc` enabled, support for cancellation using `SIGINT` signals.
+//! - With feature `signals` enabled, support for cancellation using arbitrary Unix signals
+//!   (e.g., `SIGTERM` or `SIGHUP`).
+//! - With feature `process` enabled, support for cancellation once the parent process
+//!   (or any other watched process) terminates.
+//! - With feature `file` enabled, support for cancellation once a sentinel file is created
+//!   or modified.
+//! - With feature `stdio` enabled, support for cancellation once the standard input is closed,
+//!   the standard output is broken, or a key is pressed.
+//! - With feature `control` enabled, support for cancelling (or extending the deadline of)
+//!   named scopes from outside the process using a local Unix domain socket.
 //! - With feature `pyo3` enabled, support for cancellation using `Python::check_signals`.
 //! - With feature `memory` enabled, support for cancellation based on memory consumption returned by `memory-stats`.
 //! - With feature `liveness` enabled, you can register a per-thread handler invoked
 //!   once the thread becomes unresponsive (i.e., cancellation is not checked periodically
-//!   within the desired interval).
+//!   within the desired interval). Handlers can also receive detailed events (stall duration,
+//!   check rate) with `Healthy`, `Degraded` and `Stalled` states.
+//!   A single guard can also observe other threads or a whole group of threads (see `LivenessHandle`).
+//!   Guards can also drive external heartbeats, such as the systemd watchdog or a heartbeat file
+//!   (see `HeartbeatSink`).
+//!   Responsiveness requirements of individual phases can be set using `on_liveness` scopes.
+//!   Async tasks that block their executor in a long `poll` are reported using `LivenessFuture`.
+//! - With feature `registry` enabled, a process-wide registry of active cancellation scopes
+//!   (see [`registry::snapshot`]), which also allows cancelling a scope by its id.
+//! - With feature `profile` enabled, a profiler of cancellation checks, reporting the hottest
+//!   call sites and the worst gaps between checks (see [`profile::report`]).
 //! - Practically no overhead in cancellable code when cancellation is not actively used.
 //! - Very small overhead for "atomic-based" cancellation triggers and PyO3 cancellation.
 //! - All triggers and guards generate [`log`](https://crates.io/crates/log) messages
@@ -127,7 +151,8 @@
 //! Virtually all triggers and guards provided by `cancel_this` only apply to the current
 //! thread. However, since triggers can be safely shared across threads, it is possible to
 //! transfer them from one thread to another. Note that the transferred triggers also inherently
-//! update the liveness guard of the original thread.
+//! update the liveness guard of the original thread (as long as the guard is created before
+//! the triggers are transferred).
 //!
 //! ```rust
 //! # use std::thread::JoinHandle;
@@ -159,6 +184,8 @@
 //!     t1.join().unwrap()
 //! });
 //!
+//! // The thread stops checking cancellation here, hence the monitoring must stop as well.
+//! drop(guard);
 //! assert!(result.is_err());
 //! ```
 //!
@@ -227,6 +254,12 @@
 /// Cancellation error type.
 mod error;
 
+/// Source locations of cancellation checks.
+mod call_site;
+
+/// Cleanup of entries in private global states.
+mod registration;
+
 /// Various types of triggers, including corresponding `when_*` helper functions.
 mod triggers;
 
@@ -237,12 +270,32 @@ mod liveness;
 #[cfg(feature = "liveness")]
 pub use liveness::*;
 
+/// External heartbeats (e.g., the systemd watchdog) driven by a [`LivenessGuard`].
+#[cfg(feature = "liveness")]
+mod heartbeat;
+#[cfg(feature = "liveness")]
+pub use heartbeat::HeartbeatSink;
+
+/// An opt-in registry of all active cancellation scopes in the process, intended
+/// for dashboards and debugging tools.
+#[cfg(feature = "registry")]
+pub mod registry;
+
+/// An opt-in profiler of cancellation checks, intended for tuning how often
+/// cancellation is checked.
+#[cfg(feature = "profile")]
+pub mod profile;
+
 #[cfg(not(feature = "liveness"))]
 mod liveness {
     #[derive(Clone, Default)]
     pub(crate) struct LivenessInterceptor<R: crate::CancellationTrigger + Clone>(R);
 
     impl<R: crate::CancellationTrigger + Clone> LivenessInterceptor<R> {
+        pub fn new(inner: R) -> Self {
+            LivenessInterceptor(inner)
+        }
+
         pub fn as_inner_mut(&mut self) -> &mut R {
             &mut self.0
         }
@@ -268,33 +321,125 @@ mod liveness {
         fn type_name(&self) -> &'static str {
             self.0.type_name()
         }
+
+        fn detail(&self) -> Option<String> {
+            self.0.detail()
+        }
+
+        fn check(&self) -> Option<crate::Cancelled> {
+            self.0.check()
+        }
+
+        fn subscribe(&self, listener: &crate::TriggerListener) -> bool {
+            self.0.subscribe(listener)
+        }
+
+        fn unsubscribe(&self, listener: &crate::TriggerListener) {
+            self.0.unsubscribe(listener)
+        }
     }
 }
 
+pub use call_site::CallSite;
 pub use error::*;
 use liveness::LivenessInterceptor;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 pub use triggers::*;
 
 /// The "default" [`Cancelled`] cause, reported when the trigger type is unknown.
 pub const UNKNOWN_CAUSE: &str = "UnknownCancellationTrigger";
 
 thread_local! {
-    /// The correct usage of this value lies in the fact that references to the `CancelChain`
-    /// will never leak out of this crate (we can hand out copies to the downstream users).
-    /// Within the crate, the triggers are either read when checking cancellation status or
-    /// written when entering/leaving the scope. However, these two actions are never performed
-    /// simultaneously.
-    static TRIGGER: RefCell<LivenessInterceptor<CancelChain>> = RefCell::new(LivenessInterceptor::default());
+    /// The thread-local triggers are only borrowed to obtain a shared reference (see
+    /// [`thread_triggers`]) or to update the chain when entering/leaving a scope (see
+    /// [`update_thread_triggers`]). The borrow is never held while checking the triggers,
+    /// so triggers can safely check cancellation or enter new scopes themselves. If the chain
+    /// is updated while it is being checked, the update is performed on a copy
+    /// (copy-on-write).
+    static TRIGGER: RefCell<Rc<LivenessInterceptor<CancelChain>>> =
+        RefCell::new(Rc::new(new_thread_chain()));
+
+    /// Notified whenever a check of the thread-local triggers could fail, i.e., once a trigger
+    /// of the thread-local chain is canceled (the listener is shared by all copies of the chain),
+    /// the thread is interrupted, or a global trigger is installed. Until then, a cancellation
+    /// check is a single atomic load (see [`check_local_cancellation`]).
+    static THREAD_WAKE: ThreadWake = ThreadWake::new();
+}
+
+/// The listener of the thread-local trigger chain, which is also subscribed to the interrupt
+/// flag of the thread and to the installation of global triggers.
+struct ThreadWake {
+    listener: TriggerListener,
+    interrupt: CancelInterrupt,
+}
+
+impl ThreadWake {
+    fn new() -> Self {
+        let listener = TriggerListener::default();
+        let interrupt = CancelInterrupt::current();
+        interrupt.subscribe(&listener);
+        subscribe_globals(&listener);
+        ThreadWake {
+            listener,
+            interrupt,
+        }
+    }
+}
+
+impl Drop for ThreadWake {
+    fn drop(&mut self) {
+        self.interrupt.unsubscribe(&self.listener);
+        unsubscribe_globals(&self.listener);
+    }
+}
+
+/// Create an empty thread-local trigger chain.
+pub(crate) fn new_thread_chain() -> LivenessInterceptor<CancelChain> {
+    // Once the thread is being destroyed, the chain cannot use the fast path anymore.
+    let listener = THREAD_WAKE
+        .try_with(|it| it.listener.clone())
+        .unwrap_or_default();
+    LivenessInterceptor::new(CancelChain::with_listener(listener))
+}
+
+/// Obtain a shared reference to the thread-local triggers.
+pub(crate) fn thread_triggers() -> Rc<LivenessInterceptor<CancelChain>> {
+    TRIGGER.with_borrow(Rc::clone)
+}
+
+/// Update the thread-local trigger chain. The `update` must not run any user code other than
+/// [`CancellationTrigger::subscribe`] and [`CancellationTrigger::unsubscribe`].
+pub(crate) fn update_thread_triggers<R>(update: impl FnOnce(&mut CancelChain) -> R) -> R {
+    TRIGGER.with_borrow_mut(|trigger| {
+        if Rc::get_mut(trigger).is_none() {
+            // The copy keeps the listener of the thread (see `THREAD_WAKE`).
+            let chain = trigger.as_inner();
+            let copy = chain.clone_with_listener(chain.listener().clone());
+            *trigger = Rc::new(LivenessInterceptor::new(copy));
+        }
+        let trigger = Rc::get_mut(trigger).expect("The thread-local triggers are not shared.");
+        update(trigger.as_inner_mut())
+    })
+}
+
+/// Reset the listener of the thread-local triggers (see `THREAD_WAKE`) once nothing
+/// can cancel the thread anymore, e.g., once a canceled scope is exited.
+pub(crate) fn refresh_thread_triggers() {
+    thread_triggers()
+        .as_inner()
+        .refresh_with(|| check_interrupt().is_some() || has_globals());
 }
 
 /// Call this macro every time your code wants to check for cancellation. It returns
 /// `Result<(), Cancelled>`, which can typically be propagated using the `?` operator.
 #[macro_export]
 macro_rules! is_cancelled {
-    () => {
-        $crate::check_local_cancellation()
-    };
+    () => {{
+        static CALL_SITE: $crate::CallSite =
+            $crate::CallSite::new(file!(), line!(), module_path!());
+        $crate::check_local_cancellation_at(&CALL_SITE)
+    }};
     ($handler:ident) => {
         $crate::check_cancellation(&$handler)
     };
@@ -303,13 +448,12 @@ macro_rules! is_cancelled {
 /// Returns [`Cancelled`] if [`CancellationTrigger::is_cancelled`] of the given
 /// `trigger` is true. In typical situations, you don't use this method directly,
 /// but instead use the [`is_cancelled`] macro.
-pub fn check_cancellation<TCancel: CancellationTrigger>(
+pub fn check_cancellation<TCancel: CancellationTrigger + ?Sized>(
     trigger: &TCancel,
 ) -> Result<(), Cancelled> {
-    if trigger.is_cancelled() {
-        Err(Cancelled::new(trigger.type_name()))
-    } else {
-        Ok(())
+    match trigger.check() {
+        None => Ok(()),
+        Some(cancelled) => Err(cancelled),
     }
 }
 
@@ -318,16 +462,93 @@ pub fn check_cancellation<TCancel: CancellationTrigger>(
 ///
 /// To avoid a repeated borrow of the thread-local value in performance-sensitive applications,
 /// you can use [`active_triggers`] to cache the value in a local variable.
+///
+/// Triggers can check cancellation themselves (e.g., when they wrap a cancellable library).
+/// Such nested checks only consider the scopes entered while the enclosing check is running,
+/// since the remaining triggers are already being checked.
 pub fn check_local_cancellation() -> Result<(), Cancelled> {
-    TRIGGER.with_borrow(check_cancellation)
+    #[cfg(feature = "registry")]
+    registry::record_check();
+    #[cfg(feature = "liveness")]
+    liveness::record_check();
+    if THREAD_WAKE
+        .try_with(|it| !it.listener.is_notified())
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+    let trigger = thread_triggers();
+    let chain = trigger.as_inner();
+    if let Some(checked) = CHECKED_ID.get() {
+        return match chain.check_after(checked) {
+            None => Ok(()),
+            Some(cancelled) => Err(cancelled),
+        };
+    }
+    let _checking = CheckingGuard::enter(chain);
+    // The interrupt flag is not a part of the chain (see `CancelInterrupt`).
+    match check_with_globals(|| chain.check().or_else(check_interrupt)) {
+        Some(cancelled) => Err(cancelled),
+        None => {
+            // The thread was woken up by a cancellation which no longer applies (e.g., the
+            // global trigger was uninstalled, or the trigger is set aside by `never`).
+            if chain.is_subscribed() && !has_globals() {
+                refresh_thread_triggers();
+            }
+            Ok(())
+        }
+    }
+}
+
+thread_local! {
+    /// While the thread-local triggers are being checked, this is the id of the innermost
+    /// trigger that is being checked (see [`check_local_cancellation`]).
+    static CHECKED_ID: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Marks the triggers of the given chain as "being checked" until dropped.
+struct CheckingGuard;
+
+impl CheckingGuard {
+    fn enter(chain: &CancelChain) -> CheckingGuard {
+        // An empty chain can only contain scopes entered during the check.
+        CHECKED_ID.set(Some(chain.last_id().unwrap_or(0)));
+        CheckingGuard
+    }
+}
+
+impl Drop for CheckingGuard {
+    fn drop(&mut self) {
+        CHECKED_ID.set(None);
+    }
+}
+
+/// The same as [`check_local_cancellation`], but with feature `liveness` enabled, the given
+/// `call_site` is also recorded as the last place where the thread checked cancellation
+/// (see `LivenessEvent::last_check`), and with feature `profile` enabled, the check is
+/// profiled (see `profile::report`). This is what the [`is_cancelled`] macro uses.
+pub fn check_local_cancellation_at(call_site: &'static CallSite) -> Result<(), Cancelled> {
+    #[cfg(feature = "liveness")]
+    liveness::record_call_site(call_site);
+    #[cfg(feature = "profile")]
+    profile::record(call_site);
+    #[cfg(not(any(feature = "liveness", feature = "profile")))]
+    let _ = call_site;
+    check_local_cancellation()
 }
 
 /// Get a snapshot of the current thread-local cancellation trigger.
 ///
 /// This value can be either used to initialize triggers in a new thread using [`on_trigger`],
 /// or used directly as an argument to the [`is_cancelled`] macro to speed up cancellation checks.
-pub fn active_triggers() -> DynamicCancellationTrigger {
-    TRIGGER.with_borrow(|trigger| trigger.clone_and_flatten())
+/// The snapshot is cheap to clone (see [`TriggerSnapshot`]).
+pub fn active_triggers() -> TriggerSnapshot {
+    let trigger = thread_triggers();
+    TriggerSnapshot::new(
+        trigger.clone_and_flatten(),
+        trigger.as_inner().type_names(),
+        CancelInterrupt::active(),
+    )
 }
 
 /// Run the `action` in a context where a cancellation can be signaled using the given `trigger`.
@@ -343,8 +564,180 @@ where
     TAction: FnOnce() -> Result<TResult, TError>,
     TError: From<Cancelled>,
 {
-    TRIGGER.with_borrow_mut(|thread_trigger| thread_trigger.as_inner_mut().push(trigger));
+    run_scope(None, trigger, action)
+}
+
+/// The same as [`on_trigger`], but the scope is also identified by the given `label`.
+///
+/// The label is only used by the scope [`registry`] (with feature `registry` enabled),
+/// otherwise this is equivalent to [`on_trigger`].
+pub fn on_labeled_trigger<TResult, TError, TCancel, TAction, TLabel>(
+    label: TLabel,
+    trigger: TCancel,
+    action: TAction,
+) -> Result<TResult, TError>
+where
+    TCancel: CancellationTrigger + 'static,
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+    TLabel: Into<String>,
+{
+    run_scope(Some(label.into()), trigger, action)
+}
+
+fn run_scope<TResult, TError, TCancel, TAction>(
+    label: Option<String>,
+    trigger: TCancel,
+    action: TAction,
+) -> Result<TResult, TError>
+where
+    TCancel: CancellationTrigger + 'static,
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    // The scope is listed under the name of the original trigger, not its registry wrapper.
+    let type_name = trigger.type_name();
+    #[cfg(feature = "registry")]
+    let (trigger, _registration) = {
+        let mut triggers = thread_triggers().as_inner().type_names();
+        triggers.push(type_name);
+        registry::enter(label, triggers, trigger)
+    };
+    #[cfg(not(feature = "registry"))]
+    let _ = label;
+
+    update_thread_triggers(|chain| chain.push_named(type_name, trigger));
     let result = action();
-    TRIGGER.with_borrow_mut(|thread_trigger| thread_trigger.as_inner_mut().pop());
+    // The removed trigger is dropped and the chain is refreshed only once the thread-local
+    // triggers are no longer borrowed, since both can run arbitrary trigger code.
+    let removed = update_thread_triggers(|chain| chain.remove());
+    refresh_thread_triggers();
+    drop(removed);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancelAtomic, CancelNever, Cancellable, CancellationTrigger};
+    use std::time::Duration;
+
+    /// A trigger which uses cancellation internally, e.g., because it wraps a library
+    /// that is itself cancellable.
+    #[derive(Clone, Default)]
+    struct NestedTrigger(CancelAtomic);
+
+    impl CancellationTrigger for NestedTrigger {
+        fn is_cancelled(&self) -> bool {
+            // Nested checks do not consider this trigger (or the triggers around it) again...
+            let inner: Cancellable<()> = crate::on_trigger(CancelNever, || {
+                let _active = crate::active_triggers();
+                is_cancelled!()
+            });
+            assert!(inner.is_ok());
+            // ...but scopes entered within the check apply as usual.
+            let canceled = CancelAtomic::new();
+            canceled.cancel();
+            let inner: Cancellable<()> = crate::on_trigger(canceled, || is_cancelled!());
+            assert!(inner.is_err());
+            self.0.is_cancelled()
+        }
+
+        fn type_name(&self) -> &'static str {
+            "NestedTrigger"
+        }
+    }
+
+    #[test]
+    fn nested_trigger_checks() {
+        let trigger = NestedTrigger::default();
+        let result: Cancellable<()> = crate::on_trigger(trigger.clone(), || {
+            is_cancelled!()?;
+            // Scopes entered within the scope of the nested trigger work as usual.
+            crate::on_timeout(Duration::from_secs(10), || {
+                is_cancelled!()?;
+                trigger.0.cancel();
+                is_cancelled!()
+            })
+        });
+        assert_eq!(result.unwrap_err().cause(), "NestedTrigger");
+
+        // Once all scopes are exited, the thread is not canceled.
+        assert!(is_cancelled!().is_ok());
+        assert!(crate::active_triggers().is_empty());
+    }
+
+    #[test]
+    fn cancelled_while_set_aside() {
+        let trigger = CancelAtomic::new();
+        let result: Cancellable<()> = crate::on_atomic(trigger.clone(), || {
+            crate::never(|| {
+                trigger.cancel();
+                // The check wakes up the thread, but the canceled trigger does not apply here...
+                is_cancelled!()
+            })?;
+            // ...until the `never` scope is exited.
+            is_cancelled!()
+        });
+        assert_eq!(result.unwrap_err().cause(), "CancelAtomic");
+        assert!(is_cancelled!().is_ok());
+    }
+
+    /// A trigger which counts how many times its type name was requested.
+    #[derive(Clone, Default)]
+    struct NamedTrigger(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl CancellationTrigger for NamedTrigger {
+        fn is_cancelled(&self) -> bool {
+            false
+        }
+
+        fn type_name(&self) -> &'static str {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            "NamedTrigger"
+        }
+    }
+
+    #[test]
+    fn recorded_type_names() {
+        let trigger = NamedTrigger::default();
+        let result: Cancellable<()> = crate::on_trigger(trigger.clone(), || {
+            let requested = trigger.0.load(std::sync::atomic::Ordering::SeqCst);
+            // Type names are recorded once the scope is entered; listing them runs no trigger code.
+            let active = crate::active_triggers();
+            assert_eq!(active.type_names(), &["NamedTrigger"]);
+            crate::on_timeout(Duration::from_secs(10), || {
+                assert_eq!(
+                    crate::active_triggers().type_names(),
+                    &["NamedTrigger", "CancelTimer"]
+                );
+                is_cancelled!()
+            })?;
+            assert_eq!(
+                trigger.0.load(std::sync::atomic::Ordering::SeqCst),
+                requested
+            );
+            Ok(())
+        });
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "liveness")]
+    #[test]
+    fn nested_liveness_callback() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let reported = Arc::new(AtomicBool::new(false));
+        let reported_guard = reported.clone();
+        let guard = crate::LivenessGuard::new(Duration::from_millis(10), move |_| {
+            // The callback runs on a separate thread, which has its own triggers.
+            let result: Cancellable<()> = crate::on_trigger(CancelNever, || is_cancelled!());
+            assert!(result.is_ok());
+            reported_guard.store(true, Ordering::SeqCst);
+        });
+        while !reported.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        drop(guard);
+    }
+}
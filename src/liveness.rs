@@ -1,13 +1,94 @@
-use crate::{CancelChain, CancellationTrigger, DynamicCancellationTrigger};
-use log::{trace, warn};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::mpsc::Sender;
-use std::thread::JoinHandle;
-use std::time::Duration;
+use crate::{
+    CallSite, CancelAtomic, CancelChain, CancellationTrigger, Cancelled,
+    DynamicCancellationTrigger, HeartbeatSink, TriggerListener,
+};
+use log::{debug, log, trace, warn};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::{Debug, Display, Formatter};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::task::{Context, Poll};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
 
 thread_local! {
-    static CANCELLATION_STAMP: Arc<AtomicU64> = Arc::new(AtomicU64::default());
+    static THREAD_ACTIVITY: ThreadLocalActivity = ThreadLocalActivity::default();
+}
+
+/// Marks the activity of a thread as terminated once the thread-local state is destroyed,
+/// since the activity can outlive the thread (see [`LivenessHandle`]).
+#[derive(Default)]
+struct ThreadLocalActivity(Arc<ThreadActivity>);
+
+impl Drop for ThreadLocalActivity {
+    fn drop(&mut self) {
+        self.0.terminated.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The cancellation activity of a single thread, sampled by the monitor thread. The activity
+/// is only recorded while the thread is observed by at least one [`LivenessGuard`].
+#[derive(Default)]
+struct ThreadActivity {
+    /// The number of guards observing the thread.
+    guards: AtomicUsize,
+    /// The number of checks performed by the thread itself. Only the thread writes this value,
+    /// hence it does not need to be updated atomically.
+    stamp: AtomicU64,
+    /// The number of checks of triggers transferred to other threads
+    /// (see [`crate::active_triggers`]).
+    transferred: AtomicU64,
+    /// The last place where the thread checked cancellation (or null). Only references
+    /// to `'static` call sites are stored, hence the pointer is always safe to dereference.
+    call_site: AtomicPtr<CallSite>,
+    /// The id of the guard of the innermost [`on_liveness`] scope (or zero).
+    scope: AtomicU64,
+    /// Set once the thread terminates.
+    terminated: AtomicBool,
+}
+
+impl ThreadActivity {
+    fn is_observed(&self) -> bool {
+        self.guards.load(Ordering::Relaxed) > 0
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.terminated.load(Ordering::Relaxed)
+    }
+
+    /// The total number of recorded checks.
+    fn sample(&self) -> u64 {
+        let stamp = self.stamp.load(Ordering::Relaxed);
+        stamp.wrapping_add(self.transferred.load(Ordering::Relaxed))
+    }
+}
+
+/// Record a cancellation check of the current thread (see [`crate::check_local_cancellation`]).
+pub(crate) fn record_check() {
+    let result = THREAD_ACTIVITY.try_with(|it| {
+        if it.0.is_observed() {
+            // Only this thread writes the stamp, so a load followed by a store is enough.
+            let stamp = it.0.stamp.load(Ordering::Relaxed);
+            it.0.stamp.store(stamp.wrapping_add(1), Ordering::Relaxed);
+        }
+    });
+    if let Err(e) = result {
+        warn!("`LivenessGuard` cannot update the cancellation stamp: {e:?}");
+    }
+}
+
+/// Record the `call_site` as the last place where the current thread checked cancellation.
+pub(crate) fn record_call_site(call_site: &'static CallSite) {
+    let pointer = call_site as *const CallSite as *mut CallSite;
+    let _ = THREAD_ACTIVITY.try_with(|it| {
+        if it.0.is_observed() {
+            it.0.call_site.store(pointer, Ordering::Relaxed);
+        }
+    });
 }
 
 /// Liveness guard observes [`crate::is_cancelled`] calls and reports situations where the
@@ -21,6 +102,11 @@ thread_local! {
 /// threshold reasonably high (e.g., at least a few seconds) to avoid spurious
 /// reports of inactivity.
 ///
+/// Checks are only recorded while the thread is observed by at least one guard, so code that
+/// runs without a guard pays practically nothing for the `liveness` feature. Similarly,
+/// triggers obtained using [`crate::active_triggers`] only report their checks back to the
+/// original thread if it was observed at the time they were obtained.
+///
 /// ```rust
 /// # use std::sync::Arc;
 /// # use std::sync::atomic::{AtomicBool, Ordering};
@@ -55,8 +141,7 @@ thread_local! {
 /// });
 /// ```
 pub struct LivenessGuard {
-    monitor_thread: Option<JoinHandle<()>>,
-    stop_monitor: Sender<()>,
+    id: u64,
 }
 
 impl LivenessGuard {
@@ -67,66 +152,1285 @@ impl LivenessGuard {
     /// liveness status as an argument. If the liveness status has not changed, the callback is
     /// not invoked.
     ///
+    /// All guards are observed by a single shared monitor thread, but each guard invokes its
+    /// callback on a separate thread (started once the callback is first invoked). A slow
+    /// callback thus only delays the events of its own guard: while the callback is running,
+    /// the guard is not evaluated. Callbacks of different guards can run concurrently.
+    ///
+    /// This is a simplified version of [`LivenessGuard::with_policy`], where the thread is
+    /// considered "alive" unless it did not check cancellation for the whole `threshold`.
     pub fn new<TAction: Fn(bool) + Send + Sync + 'static>(
         threshold: Duration,
         status_change: TAction,
     ) -> LivenessGuard {
-        let (sender, receiver) = std::sync::mpsc::channel();
-        let cancellation_token = CANCELLATION_STAMP.try_with(|it| it.clone()).unwrap();
-        let monitor_thread = std::thread::spawn(move || {
-            let mut is_alive = true;
-            let mut last_stamp = 0u64;
-            loop {
-                // If this is `Ok`, it means the monitor is being destroyed.
-                // If it is `Err`, it means the duration elapsed.
-                match receiver.recv_timeout(threshold) {
-                    Ok(()) => return,
-                    Err(_) => {
-                        trace!("`LivenessGuard` waking up to evaluate task activity...");
-                        let current_stamp = cancellation_token.load(Ordering::SeqCst);
-                        let new_is_alive = current_stamp != last_stamp;
-                        if new_is_alive != is_alive {
-                            is_alive = new_is_alive;
-                            last_stamp = current_stamp;
-                            status_change(is_alive);
-                        }
-                    }
+        let policy = LivenessPolicy::SINGLE_WINDOW;
+        LivenessGuard::with_policy(threshold, policy, move |event| {
+            status_change(event.state() == LivenessState::Healthy)
+        })
+    }
+
+    /// Create a new liveness guard for the current thread which reports [`LivenessEvent`]s
+    /// using the provided callback.
+    ///
+    /// The activity of the thread is evaluated once per `threshold` (a "window"), and the state
+    /// of the thread changes according to the given [`LivenessPolicy`]. The callback is only
+    /// invoked when the state changes.
+    ///
+    /// ```rust
+    /// # use std::sync::mpsc::channel;
+    /// # use std::time::Duration;
+    /// # use cancel_this::{LivenessGuard, LivenessPolicy, LivenessState};
+    /// let (sender, receiver) = channel();
+    /// let policy = LivenessPolicy { degraded_after: 1, stalled_after: 3, recovered_after: 1 };
+    /// let guard = LivenessGuard::with_policy(Duration::from_millis(20), policy, move |event| {
+    ///     sender.send((event.state(), event.stalled_for())).unwrap();
+    /// });
+    ///
+    /// // The thread does not check cancellation at all.
+    /// std::thread::sleep(Duration::from_millis(200));
+    /// drop(guard);
+    ///
+    /// let events = receiver.iter().collect::<Vec<_>>();
+    /// assert_eq!(events[0].0, LivenessState::Degraded);
+    /// assert_eq!(events[1].0, LivenessState::Stalled);
+    /// assert!(events[1].1 >= Duration::from_millis(60));
+    /// ```
+    pub fn with_policy<TAction: Fn(&LivenessEvent) + Send + Sync + 'static>(
+        threshold: Duration,
+        policy: LivenessPolicy,
+        on_event: TAction,
+    ) -> LivenessGuard {
+        LivenessGuard::builder()
+            .threshold(threshold)
+            .policy(policy)
+            .on_event(on_event)
+            .build()
+    }
+
+    /// Create a [`LivenessGuardBuilder`], which also allows configuring escalation stages
+    /// for threads that stay unresponsive, or observing other threads than the current one.
+    pub fn builder() -> LivenessGuardBuilder {
+        LivenessGuardBuilder {
+            threshold: Duration::from_secs(1),
+            policy: LivenessPolicy::default(),
+            on_event: None,
+            on_group_event: None,
+            stages: Vec::new(),
+            threads: Vec::new(),
+            heartbeats: Vec::new(),
+            scoped: false,
+        }
+    }
+
+    /// The current [`LivenessState`] of every thread observed by this guard. Threads that
+    /// have terminated are no longer observed.
+    pub fn states(&self) -> Vec<(ThreadId, LivenessState)> {
+        monitor_state()
+            .guards
+            .iter()
+            .filter(|it| it.id == self.id)
+            .flat_map(|it| it.members.iter())
+            .map(|it| (it.handle.thread_id, it.state))
+            .collect()
+    }
+}
+
+/// Run the given `action` while observing the liveness of the current thread with the given
+/// `threshold` (see [`LivenessGuard::new`]).
+///
+/// Unlike a [`LivenessGuard`], the threshold only applies within this scope: Nested scopes
+/// override the outer ones, meaning the outer scope is not evaluated until the nested scope
+/// exits (after which it continues as if the nested scope took no time). This allows each
+/// phase of a computation to have its own responsiveness requirements. Guards created
+/// directly (i.e., not using [`on_liveness`]) are not affected by scopes.
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use std::sync::atomic::{AtomicBool, Ordering};
+/// # use std::time::Duration;
+/// # use cancel_this::{is_cancelled, Cancellable};
+/// let parse_stalled = Arc::new(AtomicBool::new(false));
+/// let parse_stalled_handler = parse_stalled.clone();
+/// let result: Cancellable<()> = cancel_this::on_liveness(
+///     Duration::from_millis(20),
+///     move |is_alive| parse_stalled_handler.store(!is_alive, Ordering::SeqCst),
+///     || {
+///         // Parsing checks cancellation often...
+///         for _ in 0..10 {
+///             is_cancelled!()?;
+///             std::thread::sleep(Duration::from_millis(1));
+///         }
+///         // ...while the solver phase can take a while.
+///         cancel_this::on_liveness(Duration::from_secs(2), |_| (), || {
+///             std::thread::sleep(Duration::from_millis(100));
+///             is_cancelled!()
+///         })
+///     },
+/// );
+/// result.unwrap();
+/// assert!(!parse_stalled.load(Ordering::SeqCst));
+/// ```
+pub fn on_liveness<TResult, TAction, THandler>(
+    threshold: Duration,
+    status_change: THandler,
+    action: TAction,
+) -> TResult
+where
+    TAction: FnOnce() -> TResult,
+    THandler: Fn(bool) + Send + Sync + 'static,
+{
+    let guard = LivenessGuard::builder()
+        .threshold(threshold)
+        .policy(LivenessPolicy::SINGLE_WINDOW)
+        .on_event(move |event| status_change(event.state() == LivenessState::Healthy))
+        .scoped()
+        .build();
+    let activity = THREAD_ACTIVITY
+        .try_with(|it| it.0.clone())
+        .expect("Thread-local liveness state is not available.");
+    let previous = activity.scope.swap(guard.id, Ordering::Relaxed);
+    let _scope = LivenessScope {
+        guard,
+        activity,
+        previous,
+    };
+    action()
+}
+
+/// Restores the outer [`on_liveness`] scope once dropped (even if the action panics).
+struct LivenessScope {
+    // Dropped once the previous scope is restored.
+    #[allow(dead_code)]
+    guard: LivenessGuard,
+    activity: Arc<ThreadActivity>,
+    previous: u64,
+}
+
+impl Drop for LivenessScope {
+    fn drop(&mut self) {
+        self.activity.scope.store(self.previous, Ordering::Relaxed);
+    }
+}
+
+/// Identifies a thread which can be observed by a [`LivenessGuard`] created on another thread
+/// (see [`LivenessGuardBuilder::thread`]).
+///
+/// The handle has to be obtained by the observed thread itself using
+/// [`LivenessHandle::current`], because the state of the thread is not accessible through
+/// a [`std::thread::Thread`]. The handle can be then sent to any other thread.
+///
+/// The handle outlives the thread. Once the thread terminates, the guards observing it stop
+/// doing so, i.e., a finished thread is never reported as [`LivenessState::Stalled`].
+///
+/// ```rust
+/// # use std::sync::mpsc::channel;
+/// # use std::time::Duration;
+/// # use cancel_this::{LivenessGuard, LivenessHandle, LivenessState};
+/// let (sender, receiver) = channel();
+/// let worker = std::thread::spawn(move || {
+///     sender.send(LivenessHandle::current()).unwrap();
+///     // The worker does not check cancellation at all.
+///     std::thread::sleep(Duration::from_millis(200));
+/// });
+///
+/// let handle = receiver.recv().unwrap();
+/// let worker_id = handle.thread_id();
+/// let guard = LivenessGuard::builder()
+///     .threshold(Duration::from_millis(20))
+///     .thread(handle)
+///     .build();
+/// std::thread::sleep(Duration::from_millis(100));
+/// assert_eq!(guard.states(), vec![(worker_id, LivenessState::Stalled)]);
+/// worker.join().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct LivenessHandle {
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+    activity: Arc<ThreadActivity>,
+}
+
+impl LivenessHandle {
+    /// The handle of the current thread.
+    pub fn current() -> LivenessHandle {
+        let thread = std::thread::current();
+        LivenessHandle {
+            thread_id: thread.id(),
+            thread_name: thread.name().map(|it| it.to_string()),
+            activity: THREAD_ACTIVITY
+                .try_with(|it| it.0.clone())
+                .expect("Thread-local liveness state is not available."),
+        }
+    }
+
+    /// The id of the thread.
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// The name of the thread (if the thread is named).
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+}
+
+impl Debug for LivenessHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LivenessHandle")
+            .field("thread_id", &self.thread_id)
+            .field("thread_name", &self.thread_name)
+            .finish()
+    }
+}
+
+/// A callback which receives [`LivenessEvent`]s.
+type EventCallback = Arc<dyn Fn(&LivenessEvent) + Send + Sync>;
+
+/// A callback which receives [`LivenessGroupEvent`]s.
+type GroupEventCallback = Arc<dyn Fn(&LivenessGroupEvent) + Send + Sync>;
+
+/// Configures a [`LivenessGuard`] (see [`LivenessGuard::builder`]).
+///
+/// Besides reporting [`LivenessEvent`]s, the guard can act once the thread stays unresponsive
+/// for a long time, using an ordered list of escalation stages. Each stage is executed once
+/// the thread has not checked cancellation for the stage delay (and all previous stages were
+/// executed). Once the thread checks cancellation again, all stages are reset.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{CancelAtomic, CancellationTrigger, EscalationAction, LivenessGuard};
+/// let siblings = CancelAtomic::new();
+/// let threshold = Duration::from_millis(20);
+/// let guard = LivenessGuard::builder()
+///     .threshold(threshold)
+///     .escalate(threshold, EscalationAction::Log(log::Level::Warn))
+///     .escalate(3 * threshold, EscalationAction::Cancel(siblings.clone()))
+///     .build();
+///
+/// // The thread does not check cancellation at all.
+/// std::thread::sleep(Duration::from_millis(200));
+/// drop(guard);
+/// assert!(siblings.is_cancelled());
+/// ```
+///
+/// A single guard can also observe a group of threads (e.g., all workers of a thread pool).
+/// In that case, each member is evaluated independently, and a [`LivenessGroupEvent`] is
+/// reported once all members are stalled (and once the group recovers).
+///
+/// ```rust
+/// # use std::sync::mpsc::channel;
+/// # use std::time::Duration;
+/// # use cancel_this::{LivenessGuard, LivenessHandle};
+/// let (sender, receiver) = channel();
+/// let workers = (0..4)
+///     .map(|_| {
+///         let (handle_sender, handle_receiver) = channel();
+///         let worker = std::thread::spawn(move || {
+///             handle_sender.send(LivenessHandle::current()).unwrap();
+///             // The workers do not check cancellation at all.
+///             std::thread::sleep(Duration::from_millis(200));
+///         });
+///         (worker, handle_receiver.recv().unwrap())
+///     })
+///     .collect::<Vec<_>>();
+///
+/// let guard = LivenessGuard::builder()
+///     .threshold(Duration::from_millis(20))
+///     .threads(workers.iter().map(|(_, handle)| handle.clone()))
+///     .on_group_event(move |event| sender.send(event.is_stalled()).unwrap())
+///     .build();
+/// assert!(receiver.recv().unwrap());
+/// for (worker, _) in workers {
+///     worker.join().unwrap();
+/// }
+/// drop(guard);
+/// ```
+pub struct LivenessGuardBuilder {
+    threshold: Duration,
+    policy: LivenessPolicy,
+    on_event: Option<EventCallback>,
+    on_group_event: Option<GroupEventCallback>,
+    stages: Vec<(Duration, EscalationAction)>,
+    threads: Vec<LivenessHandle>,
+    heartbeats: Vec<HeartbeatSink>,
+    /// True if the guard is only evaluated within its [`on_liveness`] scope.
+    scoped: bool,
+}
+
+impl LivenessGuardBuilder {
+    /// The duration of a single window in which the activity of the thread is evaluated
+    /// (one second by default).
+    pub fn threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// The policy used to determine the [`LivenessState`] of the thread.
+    pub fn policy(mut self, policy: LivenessPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The callback invoked every time the [`LivenessState`] of the thread changes.
+    pub fn on_event<TAction: Fn(&LivenessEvent) + Send + Sync + 'static>(
+        mut self,
+        on_event: TAction,
+    ) -> Self {
+        self.on_event = Some(Arc::new(on_event));
+        self
+    }
+
+    /// The callback invoked once all observed threads become [`LivenessState::Stalled`], and
+    /// once at least one of them is no longer stalled.
+    pub fn on_group_event<TAction: Fn(&LivenessGroupEvent) + Send + Sync + 'static>(
+        mut self,
+        on_group_event: TAction,
+    ) -> Self {
+        self.on_group_event = Some(Arc::new(on_group_event));
+        self
+    }
+
+    /// Observe the given thread. Can be used repeatedly to observe a group of threads.
+    /// If no thread is given, the guard observes the current thread.
+    ///
+    /// Once the thread terminates, the guard stops observing it, i.e., the thread is no longer
+    /// reported (see also [`LivenessHandle`]).
+    pub fn thread(mut self, handle: LivenessHandle) -> Self {
+        self.threads.push(handle);
+        self
+    }
+
+    /// Observe all the given threads (see [`LivenessGuardBuilder::thread`]).
+    pub fn threads<I: IntoIterator<Item = LivenessHandle>>(mut self, handles: I) -> Self {
+        self.threads.extend(handles);
+        self
+    }
+
+    /// Send a heartbeat to the given `sink` once per window, as long as none of the observed
+    /// threads is [`LivenessState::Stalled`]. Can be used repeatedly to drive several sinks.
+    pub fn heartbeat(mut self, sink: HeartbeatSink) -> Self {
+        self.heartbeats.push(sink);
+        self
+    }
+
+    /// Add an escalation stage which executes the `action` once the thread has not checked
+    /// cancellation for the given `delay` (measured with the granularity of windows).
+    pub fn escalate(mut self, delay: Duration, action: EscalationAction) -> Self {
+        self.stages.push((delay, action));
+        self
+    }
+
+    /// The guard is only evaluated while its [`on_liveness`] scope is the innermost one.
+    fn scoped(mut self) -> Self {
+        self.scoped = true;
+        self
+    }
+
+    /// Start the liveness monitoring of the observed threads.
+    pub fn build(self) -> LivenessGuard {
+        MONITOR_THREAD.get_or_init(start_monitor);
+        let now = Instant::now();
+        let mut state = monitor_state();
+        let id = state.next_id;
+        state.next_id += 1;
+        let threshold = self.threshold;
+        state.guards.push(self.into_guard(id, now));
+        state.schedule.push(Reverse((now + threshold, id)));
+        drop(state);
+        MONITOR_WAKE.notify_all();
+        LivenessGuard { id }
+    }
+
+    fn into_guard(mut self, id: u64, now: Instant) -> MonitorGuard {
+        if self.threads.is_empty() {
+            self.threads.push(LivenessHandle::current());
+        }
+        let members = self
+            .threads
+            .into_iter()
+            .map(|handle| {
+                handle.activity.guards.fetch_add(1, Ordering::Relaxed);
+                MonitorEntry {
+                    threshold: self.threshold,
+                    policy: self.policy,
+                    last_stamp: handle.activity.sample(),
+                    handle,
+                    last_progress: now,
+                    scope: self.scoped.then_some(id),
+                    checks: 0,
+                    idle_windows: 0,
+                    active_windows: 0,
+                    state: LivenessState::Healthy,
+                    on_event: self.on_event.clone(),
+                    stages: self.stages.clone(),
+                    next_stage: 0,
                 }
+            })
+            .collect();
+        MonitorGuard {
+            id,
+            threshold: self.threshold,
+            members,
+            on_group_event: self.on_group_event,
+            group_stalled: false,
+            heartbeats: self.heartbeats,
+            has_panicked: false,
+        }
+    }
+}
+
+/// The action executed by an escalation stage of a [`LivenessGuard`]
+/// (see [`LivenessGuardBuilder::escalate`]).
+#[derive(Clone)]
+pub enum EscalationAction {
+    /// Invoke the callback with the current state of the thread.
+    Callback(Arc<dyn Fn(&LivenessEvent) + Send + Sync>),
+    /// Emit a `log` record with the given level.
+    Log(log::Level),
+    /// Abort the process using [`std::process::abort`] (e.g., to obtain a core dump).
+    Abort,
+    /// Cancel the given trigger (e.g., to cancel sibling work).
+    Cancel(CancelAtomic),
+}
+
+impl EscalationAction {
+    /// Create a [`EscalationAction::Callback`] from the given function.
+    pub fn callback<TAction: Fn(&LivenessEvent) + Send + Sync + 'static>(action: TAction) -> Self {
+        EscalationAction::Callback(Arc::new(action))
+    }
+
+    fn execute(&self, event: &LivenessEvent) {
+        match self {
+            EscalationAction::Callback(action) => action(event),
+            EscalationAction::Log(level) => log!(*level, "Liveness escalation: {event}."),
+            EscalationAction::Abort => {
+                warn!("Liveness escalation: {event}. Aborting.");
+                std::process::abort();
             }
+            EscalationAction::Cancel(trigger) => {
+                trace!("Liveness escalation: {event}. Canceling.");
+                trigger.cancel();
+            }
+        }
+    }
+}
+
+/// The state of a thread observed by a [`LivenessGuard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LivenessState {
+    /// The thread checks cancellation regularly.
+    Healthy,
+    /// The thread did not check cancellation for [`LivenessPolicy::degraded_after`] windows.
+    Degraded,
+    /// The thread did not check cancellation for [`LivenessPolicy::stalled_after`] windows.
+    Stalled,
+}
+
+/// Describes how the [`LivenessState`] of a thread changes (see [`LivenessGuard::with_policy`]).
+///
+/// All values are numbers of consecutive windows (i.e., threshold durations) and are treated
+/// as at least one. Requiring several windows for a change of state avoids reporting threads
+/// that oscillate around the threshold over and over again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LivenessPolicy {
+    /// A healthy thread becomes [`LivenessState::Degraded`] after this many windows without
+    /// a cancellation check.
+    pub degraded_after: u32,
+    /// A thread becomes [`LivenessState::Stalled`] after this many windows without
+    /// a cancellation check.
+    pub stalled_after: u32,
+    /// A degraded or stalled thread becomes [`LivenessState::Healthy`] after this many windows
+    /// where cancellation was checked.
+    pub recovered_after: u32,
+}
+
+impl LivenessPolicy {
+    /// The state changes after a single window (used by [`LivenessGuard::new`]).
+    const SINGLE_WINDOW: LivenessPolicy = LivenessPolicy {
+        degraded_after: 1,
+        stalled_after: 1,
+        recovered_after: 1,
+    };
+}
+
+impl Default for LivenessPolicy {
+    fn default() -> Self {
+        LivenessPolicy {
+            degraded_after: 1,
+            stalled_after: 3,
+            recovered_after: 2,
+        }
+    }
+}
+
+/// Reported by a [`LivenessGuard`] every time the [`LivenessState`] of the observed
+/// thread changes.
+#[derive(Debug, Clone)]
+pub struct LivenessEvent {
+    state: LivenessState,
+    previous: LivenessState,
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+    task_name: Option<String>,
+    stalled_for: Duration,
+    last_check: Option<&'static CallSite>,
+    checks: u64,
+    window: Duration,
+}
+
+impl LivenessEvent {
+    /// The new state of the thread.
+    pub fn state(&self) -> LivenessState {
+        self.state
+    }
+
+    /// The state of the thread before this event.
+    pub fn previous(&self) -> LivenessState {
+        self.previous
+    }
+
+    /// The id of the observed thread.
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// The name of the observed thread (if the thread is named).
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+
+    /// The name of the observed task, if the event is reported by a [`LivenessFuture`]. In that
+    /// case, the thread is the one which polled the task.
+    pub fn task_name(&self) -> Option<&str> {
+        self.task_name.as_deref()
+    }
+
+    /// The time since cancellation was last observed to be checked by the thread (measured
+    /// with the granularity of windows). Zero if cancellation was checked in the last window.
+    pub fn stalled_for(&self) -> Duration {
+        self.stalled_for
+    }
+
+    /// The place where the thread last checked cancellation using [`crate::is_cancelled`]
+    /// (if known). If the thread is stalled, this typically points to the code that is
+    /// missing a cancellation check.
+    pub fn last_check(&self) -> Option<&'static CallSite> {
+        self.last_check
+    }
+
+    /// The number of cancellation checks in the last window.
+    pub fn checks(&self) -> u64 {
+        self.checks
+    }
+
+    /// The duration of the window (i.e., the threshold of the guard).
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// The number of cancellation checks per second in the last window.
+    pub fn check_rate(&self) -> f64 {
+        self.checks as f64 / self.window.as_secs_f64()
+    }
+}
+
+impl Display for LivenessEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(task) = &self.task_name {
+            write!(f, "task `{task}` on ")?;
+        }
+        match &self.thread_name {
+            Some(name) => write!(f, "thread `{name}`")?,
+            None => write!(f, "thread {:?}", self.thread_id)?,
+        }
+        write!(f, " is {:?}, last seen ", self.state)?;
+        if let Some(call_site) = self.last_check {
+            write!(f, "at {call_site}, ")?;
+        }
+        write!(f, "{:.1}s ago", self.stalled_for.as_secs_f64())
+    }
+}
+
+/// Reported by a [`LivenessGuard`] observing a group of threads once all members of the group
+/// become [`LivenessState::Stalled`], and once at least one of them recovers
+/// (see [`LivenessGuardBuilder::on_group_event`]).
+#[derive(Debug, Clone)]
+pub struct LivenessGroupEvent {
+    stalled: bool,
+    members: Vec<LivenessEvent>,
+}
+
+impl LivenessGroupEvent {
+    /// True if all members of the group are stalled.
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// The current state of all members of the group.
+    pub fn members(&self) -> &[LivenessEvent] {
+        &self.members
+    }
+
+    /// The members of the group which are currently stalled.
+    pub fn stalled_members(&self) -> impl Iterator<Item = &LivenessEvent> {
+        self.members
+            .iter()
+            .filter(|it| it.state == LivenessState::Stalled)
+    }
+}
+
+/// Adapts a future such that its polls are observed by the liveness monitor
+/// (see [`LivenessFuture::new`]).
+///
+/// A [`LivenessGuard`] observes threads, meaning an async task that blocks its executor thread
+/// in a long `poll` is only visible as a stalled executor thread (if at all). Instead, this
+/// adapter measures every poll of the task and reports it as [`LivenessState::Stalled`] once
+/// a single poll takes longer than the threshold (which typically means the task runs blocking
+/// code). Once a subsequent poll completes within the threshold, the task is reported
+/// as [`LivenessState::Healthy`] again. Cancellation checks performed during the polls are
+/// attributed to the task (see [`LivenessEvent::checks`] and [`LivenessEvent::last_check`]).
+///
+/// The long poll is reported by a callback thread of the task while it is still running,
+/// or by the polling thread once the poll completes (whichever happens first).
+///
+/// ```rust
+/// # use std::sync::mpsc::channel;
+/// # use std::task::{Context, Poll, Waker};
+/// # use std::time::Duration;
+/// # use cancel_this::{LivenessFuture, LivenessState};
+/// let (sender, receiver) = channel();
+/// let task = async {
+///     // Blocking code in an async context.
+///     std::thread::sleep(Duration::from_millis(100));
+/// };
+/// let mut task = LivenessFuture::new("import", Duration::from_millis(20), task, move |event| {
+///     sender.send((event.state(), event.task_name().unwrap().to_string())).unwrap();
+/// });
+///
+/// let mut context = Context::from_waker(Waker::noop());
+/// assert!(std::pin::Pin::new(&mut task).poll(&mut context).is_ready());
+/// assert_eq!(receiver.recv().unwrap(), (LivenessState::Stalled, "import".to_string()));
+/// ```
+pub struct LivenessFuture<F: Future> {
+    id: u64,
+    inner: Pin<Box<F>>,
+    task: Arc<TaskEntry>,
+}
+
+impl<F: Future> LivenessFuture<F> {
+    /// Observe the polls of the given `future` (identified by its `name`), reporting polls that
+    /// take longer than `threshold` using the `on_event` callback.
+    pub fn new<TAction: Fn(&LivenessEvent) + Send + Sync + 'static>(
+        name: impl Into<String>,
+        threshold: Duration,
+        future: F,
+        on_event: TAction,
+    ) -> Self {
+        let task = Arc::new(TaskEntry {
+            name: name.into(),
+            threshold,
+            on_event: Arc::new(on_event),
+            state: Mutex::new(TaskState {
+                handle: None,
+                poll: None,
+                state: LivenessState::Healthy,
+                scheduled: false,
+            }),
         });
-        LivenessGuard {
-            monitor_thread: Some(monitor_thread),
-            stop_monitor: sender,
+        MONITOR_THREAD.get_or_init(start_monitor);
+        let mut state = monitor_state();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.tasks.push((id, task.clone()));
+        drop(state);
+        LivenessFuture {
+            id,
+            inner: Box::pin(future),
+            task,
+        }
+    }
+}
+
+impl<F: Future> Future for LivenessFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let poll = TaskPoll::start(this.id, &this.task);
+        let result = this.inner.as_mut().poll(cx);
+        if let Some(event) = poll.finish() {
+            (this.task.on_event)(&event);
         }
+        result
+    }
+}
+
+impl<F: Future> Drop for LivenessFuture<F> {
+    fn drop(&mut self) {
+        let Some(mut state) = idle_monitor_state(self.id) else {
+            return;
+        };
+        // The scheduled check (if any) is skipped once it is due.
+        state.tasks.retain(|(id, _)| *id != self.id);
     }
 }
 
 impl Drop for LivenessGuard {
     fn drop(&mut self) {
-        let thread = self
-            .monitor_thread
-            .take()
-            .expect("Invariant violation: Monitor thread removed before drop.");
-
-        let join = match self.stop_monitor.send(()) {
-            Ok(()) => thread.join(),
-            Err(_) => {
-                // The receiver has already been deallocated, meaning the monitor is most likely dead.
-                if !thread.is_finished() {
-                    warn!("`LivenessGuard` cannot be stopped. Possible thread leak.`");
-                    return;
-                } else {
-                    thread.join()
+        let Some(mut state) = idle_monitor_state(self.id) else {
+            return;
+        };
+        let removed = state
+            .guards
+            .iter()
+            .position(|it| it.id == self.id)
+            .map(|index| state.guards.swap_remove(index));
+        // The scheduled check (if any) is skipped once it is due.
+        drop(state);
+        let Some(removed) = removed else {
+            return;
+        };
+        for member in &removed.members {
+            member
+                .handle
+                .activity
+                .guards
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+        if removed.has_panicked {
+            // The callback panicked, meaning we probably want to propagate it.
+            panic!("Status change callback of `LivenessGuard` panicked.");
+        }
+    }
+}
+
+/// A single [`LivenessGuard`], observing one or more threads.
+struct MonitorGuard {
+    id: u64,
+    threshold: Duration,
+    members: Vec<MonitorEntry>,
+    on_group_event: Option<GroupEventCallback>,
+    /// True if all members were stalled during the last evaluation.
+    group_stalled: bool,
+    heartbeats: Vec<HeartbeatSink>,
+    has_panicked: bool,
+}
+
+/// A single thread observed by a [`MonitorGuard`].
+struct MonitorEntry {
+    threshold: Duration,
+    policy: LivenessPolicy,
+    handle: LivenessHandle,
+    last_stamp: u64,
+    last_progress: Instant,
+    /// The id of the guard if it is bound to an [`on_liveness`] scope.
+    scope: Option<u64>,
+    /// The number of cancellation checks in the last window.
+    checks: u64,
+    /// The number of consecutive windows without a cancellation check.
+    idle_windows: u32,
+    /// The number of consecutive windows with a cancellation check.
+    active_windows: u32,
+    state: LivenessState,
+    on_event: Option<EventCallback>,
+    stages: Vec<(Duration, EscalationAction)>,
+    /// The index of the next escalation stage to execute.
+    next_stage: usize,
+}
+
+impl MonitorEntry {
+    /// Evaluate the activity in the last window and return an event if the state changed.
+    fn evaluate(&mut self, now: Instant) -> Option<LivenessEvent> {
+        let current_stamp = self.handle.activity.sample();
+        self.checks = current_stamp.wrapping_sub(self.last_stamp);
+        self.last_stamp = current_stamp;
+        let active_scope = self.handle.activity.scope.load(Ordering::Relaxed);
+        if self.scope.is_some_and(|it| it != active_scope) {
+            // The scope is overridden by a nested scope, whose duration does not count.
+            self.checks = 0;
+            self.last_progress = now;
+            self.idle_windows = 0;
+            self.next_stage = 0;
+            return None;
+        }
+        if self.checks > 0 {
+            self.last_progress = now;
+            self.idle_windows = 0;
+            self.active_windows = self.active_windows.saturating_add(1);
+            self.next_stage = 0;
+        } else {
+            self.active_windows = 0;
+            self.idle_windows = self.idle_windows.saturating_add(1);
+        }
+
+        let policy = &self.policy;
+        let new_state = if self.active_windows >= policy.recovered_after.max(1) {
+            LivenessState::Healthy
+        } else if self.idle_windows >= policy.stalled_after.max(1) {
+            LivenessState::Stalled
+        } else if self.idle_windows >= policy.degraded_after.max(1)
+            && self.state == LivenessState::Healthy
+        {
+            LivenessState::Degraded
+        } else {
+            self.state
+        };
+        if new_state == self.state {
+            return None;
+        }
+        let previous = self.state;
+        self.state = new_state;
+        Some(self.event(previous, now))
+    }
+
+    /// Collect the actions of all escalation stages that are due.
+    fn escalate(&mut self, now: Instant) -> Vec<EscalationAction> {
+        let mut actions = Vec::new();
+        if self.idle_windows == 0 {
+            return actions;
+        }
+        let stalled_for = now - self.last_progress;
+        while let Some((delay, action)) = self.stages.get(self.next_stage) {
+            if *delay > stalled_for {
+                break;
+            }
+            actions.push(action.clone());
+            self.next_stage += 1;
+        }
+        actions
+    }
+
+    fn event(&self, previous: LivenessState, now: Instant) -> LivenessEvent {
+        LivenessEvent {
+            state: self.state,
+            previous,
+            thread_id: self.handle.thread_id,
+            thread_name: self.handle.thread_name.clone(),
+            task_name: None,
+            stalled_for: now - self.last_progress,
+            // SAFETY: Only references to `'static` call sites are stored in the pointer.
+            last_check: unsafe {
+                self.handle
+                    .activity
+                    .call_site
+                    .load(Ordering::Relaxed)
+                    .as_ref()
+            },
+            checks: self.checks,
+            window: self.threshold,
+        }
+    }
+}
+
+struct MonitorState {
+    next_id: u64,
+    guards: Vec<MonitorGuard>,
+    tasks: Vec<(u64, Arc<TaskEntry>)>,
+    /// Scheduled liveness checks, the earliest first. Checks of dropped guards are skipped.
+    schedule: BinaryHeap<Reverse<(Instant, u64)>>,
+    /// The guards (or tasks) whose callbacks are currently being invoked.
+    running: Vec<u64>,
+    /// The threads invoking the callbacks of individual guards (or tasks).
+    deliveries: Vec<(u64, Delivery)>,
+}
+
+/// Private global state of the liveness monitor thread.
+static MONITOR_STATE: Mutex<MonitorState> = Mutex::new(MonitorState {
+    // Zero is reserved for "no scope" (see `ThreadActivity::scope`).
+    next_id: 1,
+    guards: Vec::new(),
+    tasks: Vec::new(),
+    schedule: BinaryHeap::new(),
+    running: Vec::new(),
+    deliveries: Vec::new(),
+});
+
+/// Notifies the monitor thread that the schedule changed, and the dropped guards that
+/// a callback finished.
+static MONITOR_WAKE: Condvar = Condvar::new();
+
+/// The monitor thread, started once the first guard is created.
+static MONITOR_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+fn monitor_state() -> MutexGuard<'static, MonitorState> {
+    MONITOR_STATE
+        .lock()
+        .expect("Global state of the liveness monitor is corrupted.")
+}
+
+/// Start the monitor thread. Once started, the thread runs until the application is terminated.
+fn start_monitor() -> ThreadId {
+    std::thread::Builder::new()
+        .name("cancel-this-liveness".to_string())
+        .spawn(run_monitor)
+        .expect("Cannot start the `LivenessGuard` monitor thread.")
+        .thread()
+        .id()
+}
+
+/// Lock the monitor state once no callback of the guard (or task) with the given `id` is
+/// running, unless this is the thread invoking the callbacks (i.e., the guard is dropped by
+/// the callback itself). The callback thread of the guard is stopped once the returned state
+/// is released. Returns `None` if the lock is poisoned, in which case the cleanup is skipped
+/// (see [`crate::registration::Registration`]).
+fn idle_monitor_state(id: u64) -> Option<MutexGuard<'static, MonitorState>> {
+    let mut state = MONITOR_STATE.lock().ok()?;
+    let current = std::thread::current().id();
+    let is_delivery = state
+        .deliveries
+        .iter()
+        .any(|(it, delivery)| *it == id && delivery.thread == current);
+    while !is_delivery && state.running.contains(&id) {
+        state = MONITOR_WAKE.wait(state).ok()?;
+    }
+    state.deliveries.retain(|(it, _)| *it != id);
+    Some(state)
+}
+
+/// A thread which invokes the callbacks of a single guard (or task), such that a slow
+/// callback does not delay the monitor thread (and thus the other guards).
+struct Delivery {
+    thread: ThreadId,
+    sender: Sender<Box<dyn FnOnce() + Send>>,
+}
+
+/// Invoke the `callbacks` of the guard (or task) with the given `id` on its callback thread,
+/// which is started once the guard first needs it. Once the callbacks finish, the `id`
+/// is removed from [`MonitorState::running`].
+fn deliver(state: &mut MonitorState, id: u64, callbacks: impl FnOnce() + Send + 'static) {
+    state.running.push(id);
+    let callbacks: Box<dyn FnOnce() + Send> = Box::new(move || {
+        callbacks();
+        let mut state = monitor_state();
+        state.running.retain(|it| *it != id);
+        MONITOR_WAKE.notify_all();
+    });
+    if !state.deliveries.iter().any(|(it, _)| *it == id) {
+        let (sender, receiver) = channel::<Box<dyn FnOnce() + Send>>();
+        let thread = std::thread::Builder::new()
+            .name("cancel-this-liveness-callback".to_string())
+            .spawn(move || receiver.iter().for_each(|callbacks| callbacks()))
+            .expect("Cannot start the `LivenessGuard` callback thread.")
+            .thread()
+            .id();
+        state.deliveries.push((id, Delivery { thread, sender }));
+    }
+    let (_, delivery) = state
+        .deliveries
+        .iter()
+        .find(|(it, _)| *it == id)
+        .expect("The callback thread is started.");
+    // The thread only stops once the sender is dropped.
+    delivery
+        .sender
+        .send(callbacks)
+        .expect("The callback thread is running.");
+}
+
+fn run_monitor() {
+    let mut state = monitor_state();
+    loop {
+        let now = Instant::now();
+        let deadline = state.schedule.peek().map(|Reverse((it, _))| *it);
+        state = match deadline {
+            None => MONITOR_WAKE
+                .wait(state)
+                .expect("Global state of the liveness monitor is corrupted."),
+            Some(deadline) if deadline > now => {
+                MONITOR_WAKE
+                    .wait_timeout(state, deadline - now)
+                    .expect("Global state of the liveness monitor is corrupted.")
+                    .0
+            }
+            Some(_) => {
+                let Some(Reverse((_, id))) = state.schedule.pop() else {
+                    continue;
+                };
+                check_guard(state, id, now)
+            }
+        };
+    }
+}
+
+/// Evaluate the activity of the guard with the given `id` and invoke its callback if the
+/// liveness changed. The callback is invoked by the callback thread of the guard
+/// (see [`deliver`]).
+fn check_guard(
+    mut state: MutexGuard<'static, MonitorState>,
+    id: u64,
+    now: Instant,
+) -> MutexGuard<'static, MonitorState> {
+    let is_running = state.running.contains(&id);
+    let Some(guard) = state
+        .guards
+        .iter_mut()
+        .find(|it| it.id == id && !it.has_panicked)
+    else {
+        return check_task(state, id, now);
+    };
+    let next_check = now + guard.threshold;
+    if is_running {
+        // The callbacks are still processing the previous change, so the guard
+        // is evaluated once they finish.
+        state.schedule.push(Reverse((next_check, id)));
+        return state;
+    }
+    trace!("`LivenessGuard` waking up to evaluate task activity...");
+    guard.members.retain(|member| {
+        let activity = &member.handle.activity;
+        if !activity.is_terminated() {
+            return true;
+        }
+        debug!(
+            "`LivenessGuard` stops observing terminated thread {:?}.",
+            member.handle.thread_id
+        );
+        activity.guards.fetch_sub(1, Ordering::Relaxed);
+        false
+    });
+    let mut calls = Vec::new();
+    for member in &mut guard.members {
+        let event = member.evaluate(now);
+        let actions = member.escalate(now);
+        if event.is_some() || !actions.is_empty() {
+            let current = member.event(member.state, now);
+            calls.push((member.on_event.clone(), event, actions, current));
+        }
+    }
+    // A group whose members all terminated is not stalled.
+    let group_stalled = !guard.members.is_empty()
+        && guard
+            .members
+            .iter()
+            .all(|it| it.state == LivenessState::Stalled);
+    let mut group_event = None;
+    if group_stalled != guard.group_stalled {
+        guard.group_stalled = group_stalled;
+        if let Some(on_group_event) = guard.on_group_event.clone() {
+            let event = LivenessGroupEvent {
+                stalled: group_stalled,
+                members: guard
+                    .members
+                    .iter()
+                    .map(|it| it.event(it.state, now))
+                    .collect(),
+            };
+            group_event = Some((on_group_event, event));
+        }
+    }
+    let heartbeats = if guard
+        .members
+        .iter()
+        .any(|it| it.state == LivenessState::Stalled)
+    {
+        Vec::new()
+    } else {
+        guard.heartbeats.clone()
+    };
+    state.schedule.push(Reverse((next_check, id)));
+    if calls.is_empty() && group_event.is_none() && heartbeats.is_empty() {
+        return state;
+    }
+
+    deliver(&mut state, id, move || {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            for (on_event, event, actions, current) in calls {
+                if let (Some(on_event), Some(event)) = (on_event, event) {
+                    on_event(&event);
+                }
+                for action in actions {
+                    action.execute(&current);
                 }
             }
+            if let Some((on_group_event, event)) = group_event {
+                on_group_event(&event);
+            }
+            for sink in heartbeats {
+                if let Err(e) = sink.beat() {
+                    warn!("`LivenessGuard` cannot send a heartbeat to `{sink:?}`: {e}");
+                }
+            }
+        }));
+        if result.is_err() {
+            // The guard is no longer observed and the panic is propagated once it is dropped.
+            let mut state = monitor_state();
+            if let Some(guard) = state.guards.iter_mut().find(|it| it.id == id) {
+                guard.has_panicked = true;
+            }
+        }
+    });
+    state
+}
+
+/// A single [`LivenessFuture`] observed by the monitor thread.
+struct TaskEntry {
+    name: String,
+    threshold: Duration,
+    on_event: EventCallback,
+    state: Mutex<TaskState>,
+}
+
+struct TaskState {
+    /// The thread which polled the task last time.
+    handle: Option<LivenessHandle>,
+    /// The poll which is currently running (if any).
+    poll: Option<RunningPoll>,
+    state: LivenessState,
+    /// True if a check of the task is scheduled in the monitor.
+    scheduled: bool,
+}
+
+struct RunningPoll {
+    started: Instant,
+    /// The activity sample of the polling thread when the poll started.
+    start_sample: u64,
+}
+
+impl TaskEntry {
+    fn lock(&self) -> MutexGuard<'_, TaskState> {
+        self.state
+            .lock()
+            .expect("Task state of the liveness monitor is corrupted.")
+    }
+}
+
+impl TaskState {
+    fn event(&self, task: &TaskEntry, previous: LivenessState, now: Instant) -> LivenessEvent {
+        let handle = self
+            .handle
+            .as_ref()
+            .expect("Invariant violation: Task event without a polling thread.");
+        let (stalled_for, checks) = match &self.poll {
+            Some(poll) => (
+                now - poll.started,
+                handle.activity.sample().wrapping_sub(poll.start_sample),
+            ),
+            None => (Duration::ZERO, 0),
         };
-        if join.is_err() {
-            // The thread panicked, meaning we probably want to propagate it.
-            panic!("Monitor thread of `LivenessGuard` panicked.");
+        LivenessEvent {
+            state: self.state,
+            previous,
+            thread_id: handle.thread_id,
+            thread_name: handle.thread_name.clone(),
+            task_name: Some(task.name.clone()),
+            stalled_for,
+            // SAFETY: Only references to `'static` call sites are stored in the pointer.
+            last_check: unsafe { handle.activity.call_site.load(Ordering::Relaxed).as_ref() },
+            checks,
+            window: task.threshold,
         }
     }
 }
 
+/// A single poll of a [`LivenessFuture`]. While the poll is running, the polling thread
+/// is observed, such that its cancellation checks are recorded.
+struct TaskPoll<'a> {
+    id: u64,
+    task: &'a TaskEntry,
+    activity: Arc<ThreadActivity>,
+}
+
+impl<'a> TaskPoll<'a> {
+    fn start(id: u64, task: &'a TaskEntry) -> Self {
+        let now = Instant::now();
+        let mut state = task.lock();
+        let current = std::thread::current().id();
+        if state.handle.as_ref().map(|it| it.thread_id) != Some(current) {
+            state.handle = Some(LivenessHandle::current());
+        }
+        let activity = state.handle.as_ref().unwrap().activity.clone();
+        activity.guards.fetch_add(1, Ordering::Relaxed);
+        state.poll = Some(RunningPoll {
+            started: now,
+            start_sample: activity.sample(),
+        });
+        // At most one check is scheduled, such that fast polls do not flood the monitor.
+        let schedule = !state.scheduled;
+        state.scheduled = true;
+        drop(state);
+        if schedule {
+            monitor_state()
+                .schedule
+                .push(Reverse((now + task.threshold, id)));
+            MONITOR_WAKE.notify_all();
+        }
+        TaskPoll { id, task, activity }
+    }
+
+    /// Complete the poll and return an event if the state of the task changed.
+    fn finish(self) -> Option<LivenessEvent> {
+        let now = Instant::now();
+        let mut state = self.task.lock();
+        let poll = state.poll.as_ref()?;
+        let previous = state.state;
+        if poll.started + self.task.threshold > now {
+            // Healthy tasks are not stalled, hence the event does not describe the poll.
+            state.poll = None;
+            state.state = LivenessState::Healthy;
+        } else {
+            state.state = LivenessState::Stalled;
+        }
+        // If the poll took too long, it might have been already reported by the monitor.
+        let event = (state.state != previous).then(|| state.event(self.task, previous, now));
+        state.poll = None;
+        if event.is_some() {
+            trace!("`LivenessFuture[{}]` became {:?}.", self.id, state.state);
+        }
+        event
+    }
+}
+
+impl Drop for TaskPoll<'_> {
+    fn drop(&mut self) {
+        // Executed even if the poll panics.
+        self.activity.guards.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Report the running poll of the task with the given `id` if it takes too long. The callback
+/// is invoked by the callback thread of the task (see [`deliver`]).
+fn check_task(
+    mut state: MutexGuard<'static, MonitorState>,
+    id: u64,
+    now: Instant,
+) -> MutexGuard<'static, MonitorState> {
+    let Some((_, task)) = state.tasks.iter().find(|(it, _)| *it == id) else {
+        return state;
+    };
+    let task = task.clone();
+    let mut task_state = task.lock();
+    task_state.scheduled = false;
+    let Some(poll) = task_state.poll.as_ref() else {
+        return state;
+    };
+    let deadline = poll.started + task.threshold;
+    if deadline > now {
+        // The check was scheduled by an earlier poll.
+        task_state.scheduled = true;
+        state.schedule.push(Reverse((deadline, id)));
+        return state;
+    }
+    let previous = task_state.state;
+    if previous == LivenessState::Stalled {
+        return state;
+    }
+    task_state.state = LivenessState::Stalled;
+    let event = task_state.event(&task, previous, now);
+    drop(task_state);
+    trace!(
+        "`LivenessFuture[{id}]` became {:?}.",
+        LivenessState::Stalled
+    );
+    deliver(&mut state, id, move || {
+        // Unlike the callbacks of guards, a panic of the task callback is just logged, since
+        // it cannot be propagated to the task once it is dropped.
+        if catch_unwind(AssertUnwindSafe(|| (task.on_event)(&event))).is_err() {
+            warn!("Status change callback of `LivenessFuture` panicked.");
+        }
+    });
+    state
+}
+
 #[derive(Clone, Default)]
 pub(crate) struct LivenessInterceptor<R: CancellationTrigger + Clone>(R);
 
@@ -135,7 +1439,7 @@ pub(crate) struct LivenessInterceptor<R: CancellationTrigger + Clone>(R);
 #[derive(Clone)]
 pub(crate) struct TransferredLivenessInterceptor<R: CancellationTrigger + Clone> {
     inner: R,
-    stamp: Arc<AtomicU64>,
+    activity: Arc<ThreadActivity>,
 }
 
 impl<R: CancellationTrigger + Clone> LivenessInterceptor<R> {
@@ -146,16 +1450,27 @@ impl<R: CancellationTrigger + Clone> LivenessInterceptor<R> {
     pub fn as_inner(&self) -> &R {
         &self.0
     }
+
+    pub fn new(inner: R) -> Self {
+        LivenessInterceptor(inner)
+    }
+
+    fn update_stamp(&self) {
+        record_check();
+    }
 }
 
 impl LivenessInterceptor<CancelChain> {
     pub fn clone_and_flatten(&self) -> DynamicCancellationTrigger {
         let chain = self.as_inner().clone_and_flatten();
-        match CANCELLATION_STAMP.try_with(Arc::clone) {
-            Ok(stamp) => Box::new(TransferredLivenessInterceptor {
+        match THREAD_ACTIVITY.try_with(|it| it.0.clone()) {
+            // Liveness is only transferred if the thread is observed (otherwise, there is no
+            // need to slow down the checks of the copy).
+            Ok(activity) if activity.is_observed() => Box::new(TransferredLivenessInterceptor {
                 inner: chain,
-                stamp,
+                activity,
             }),
+            Ok(_) => chain,
             Err(e) => {
                 warn!("`LivenessGuard` cannot access the cancellation stamp: {e:?}");
                 chain
@@ -166,25 +1481,422 @@ impl LivenessInterceptor<CancelChain> {
 
 impl<R: CancellationTrigger + Clone> CancellationTrigger for LivenessInterceptor<R> {
     fn is_cancelled(&self) -> bool {
-        let result = CANCELLATION_STAMP.try_with(|it| it.fetch_add(1, Ordering::SeqCst));
-        if let Err(e) = result {
-            warn!("`LivenessGuard` cannot update the cancellation stamp: {e:?}");
-        }
+        self.update_stamp();
         self.0.is_cancelled()
     }
 
     fn type_name(&self) -> &'static str {
         self.0.type_name()
     }
+
+    fn detail(&self) -> Option<String> {
+        self.0.detail()
+    }
+
+    fn check(&self) -> Option<Cancelled> {
+        self.update_stamp();
+        self.0.check()
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.0.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.unsubscribe(listener)
+    }
+}
+
+impl<R: CancellationTrigger + Clone> TransferredLivenessInterceptor<R> {
+    fn update_stamp(&self) {
+        if self.activity.is_observed() {
+            self.activity.transferred.fetch_add(1, Ordering::Relaxed);
+        }
+    }
 }
 
 impl<R: CancellationTrigger + Clone> CancellationTrigger for TransferredLivenessInterceptor<R> {
     fn is_cancelled(&self) -> bool {
-        self.stamp.fetch_add(1, Ordering::SeqCst);
+        self.update_stamp();
         self.inner.is_cancelled()
     }
 
     fn type_name(&self) -> &'static str {
         self.inner.type_name()
     }
+
+    fn detail(&self) -> Option<String> {
+        self.inner.detail()
+    }
+
+    fn check(&self) -> Option<Cancelled> {
+        self.update_stamp();
+        self.inner.check()
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        // Every check has to update the stamp, so the trigger cannot be skipped by the chain.
+        self.inner.subscribe(listener);
+        false
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.inner.unsubscribe(listener)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CancelAtomic, CancellationTrigger, EscalationAction, LivenessFuture, LivenessGuard,
+        LivenessHandle, LivenessState, on_liveness,
+    };
+    use std::pin::Pin;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc::channel;
+    use std::task::{Context, Poll, Waker};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn liveness_hysteresis() {
+        let window = Duration::from_millis(100);
+        let start = Instant::now();
+        let mut entry = LivenessGuard::builder()
+            .threshold(window)
+            .into_guard(0, start)
+            .members
+            .remove(0);
+        entry.handle.thread_name = Some("worker".to_string());
+        // Simulate `checks` cancellation checks during the next window.
+        let mut time = start;
+        let mut next_window = |checks: u64| {
+            entry
+                .handle
+                .activity
+                .transferred
+                .fetch_add(checks, Ordering::SeqCst);
+            time += window;
+            entry.evaluate(time).map(|it| (it.state(), it))
+        };
+
+        assert!(next_window(5).is_none());
+        let (state, event) = next_window(0).unwrap();
+        assert_eq!(state, LivenessState::Degraded);
+        assert_eq!(event.previous(), LivenessState::Healthy);
+        assert_eq!(event.thread_name(), Some("worker"));
+        assert_eq!(event.stalled_for(), window);
+        assert!(next_window(0).is_none());
+        let (state, event) = next_window(0).unwrap();
+        assert_eq!(state, LivenessState::Stalled);
+        assert_eq!(event.stalled_for(), 3 * window);
+
+        // A single active window is not enough to recover.
+        assert!(next_window(1).is_none());
+        assert!(next_window(0).is_none());
+        assert!(next_window(1).is_none());
+        let (state, event) = next_window(20).unwrap();
+        assert_eq!(state, LivenessState::Healthy);
+        assert_eq!(event.previous(), LivenessState::Stalled);
+        assert_eq!(event.checks(), 20);
+        assert_eq!(event.check_rate(), 200.0);
+        assert_eq!(event.stalled_for(), Duration::ZERO);
+    }
+
+    #[test]
+    fn liveness_call_site() {
+        let start = Instant::now();
+        let mut entry = LivenessGuard::builder()
+            .into_guard(0, start)
+            .members
+            .remove(0);
+        entry.handle.thread_name = Some("solver".to_string());
+        let line = line!() + 1;
+        crate::is_cancelled!().unwrap();
+
+        let event = entry.event(LivenessState::Stalled, start + Duration::from_millis(4200));
+        let call_site = event.last_check().unwrap();
+        assert_eq!((call_site.file(), call_site.line()), (file!(), line));
+        assert_eq!(call_site.module_path(), module_path!());
+        assert_eq!(
+            event.to_string(),
+            format!(
+                "thread `solver` is Healthy, last seen at {}:{line}, 4.2s ago",
+                file!()
+            )
+        );
+    }
+
+    #[test]
+    fn liveness_escalation() {
+        let window = Duration::from_millis(100);
+        let start = Instant::now();
+        let siblings = CancelAtomic::new();
+        let mut entry = LivenessGuard::builder()
+            .threshold(window)
+            .escalate(window, EscalationAction::Log(log::Level::Warn))
+            .escalate(3 * window, EscalationAction::Cancel(siblings.clone()))
+            .escalate(3 * window, EscalationAction::callback(|_| ()))
+            .into_guard(0, start)
+            .members
+            .remove(0);
+        // Simulate `checks` cancellation checks during the next window and return
+        // the number of executed stages.
+        let mut time = start;
+        let mut next_window = |checks: u64| {
+            entry
+                .handle
+                .activity
+                .transferred
+                .fetch_add(checks, Ordering::SeqCst);
+            time += window;
+            entry.evaluate(time);
+            entry.escalate(time).len()
+        };
+
+        assert_eq!(next_window(1), 0);
+        assert_eq!(next_window(0), 1);
+        assert_eq!(next_window(0), 0);
+        assert_eq!(next_window(0), 2);
+        assert_eq!(next_window(0), 0);
+        // Stages are reset once the thread becomes responsive again.
+        assert_eq!(next_window(1), 0);
+        assert_eq!(next_window(0), 1);
+
+        // Actions are executed by the callback thread of the guard.
+        let guard = LivenessGuard::builder()
+            .threshold(Duration::from_millis(10))
+            .escalate(Duration::ZERO, EscalationAction::Cancel(siblings.clone()))
+            .build();
+        while !siblings.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        drop(guard);
+    }
+
+    #[test]
+    fn liveness_only_when_observed() {
+        std::thread::spawn(|| {
+            let activity = super::THREAD_ACTIVITY.with(|it| it.0.clone());
+            crate::is_cancelled!().unwrap();
+            assert_eq!(activity.sample(), 0);
+            assert!(activity.call_site.load(Ordering::Relaxed).is_null());
+
+            let guard = LivenessGuard::new(Duration::from_secs(10), |_| ());
+            crate::is_cancelled!().unwrap();
+            crate::is_cancelled!().unwrap();
+            assert_eq!(activity.sample(), 2);
+            assert!(!activity.call_site.load(Ordering::Relaxed).is_null());
+
+            // Stamping stops once the last guard is dropped.
+            drop(guard);
+            crate::is_cancelled!().unwrap();
+            assert_eq!(activity.sample(), 2);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn liveness_group() {
+        let (sender, receiver) = channel();
+        let stop = CancelAtomic::new();
+        let spawn = |active: Duration| {
+            let (handle_sender, handle_receiver) = channel();
+            let stop = stop.clone();
+            let worker = std::thread::spawn(move || {
+                handle_sender.send(LivenessHandle::current()).unwrap();
+                // Check cancellation regularly until `active` elapses, then stall.
+                let start = Instant::now();
+                while !stop.is_cancelled() {
+                    if start.elapsed() < active {
+                        crate::is_cancelled!().unwrap();
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            });
+            (worker, handle_receiver.recv().unwrap())
+        };
+        let (stalled, stalled_handle) = spawn(Duration::ZERO);
+        let (active, active_handle) = spawn(Duration::from_millis(300));
+
+        let guard = LivenessGuard::builder()
+            .threshold(Duration::from_millis(20))
+            .thread(stalled_handle.clone())
+            .thread(active_handle.clone())
+            .on_group_event(move |event| sender.send(event.clone()).unwrap())
+            .build();
+        std::thread::sleep(Duration::from_millis(150));
+        // Only one member is stalled, hence no group event.
+        let states = guard.states();
+        assert!(states.contains(&(stalled_handle.thread_id(), LivenessState::Stalled)));
+        assert!(states.contains(&(active_handle.thread_id(), LivenessState::Healthy)));
+        assert!(receiver.try_recv().is_err());
+
+        let event = receiver.recv().unwrap();
+        assert!(event.is_stalled());
+        assert_eq!(event.members().len(), 2);
+        assert_eq!(event.stalled_members().count(), 2);
+        stop.cancel();
+        drop(guard);
+        stalled.join().unwrap();
+        active.join().unwrap();
+    }
+
+    #[test]
+    fn liveness_terminated_thread() {
+        let (sender, receiver) = channel();
+        let worker = std::thread::spawn(LivenessHandle::current);
+        let handle = worker.join().unwrap();
+        assert!(handle.activity.is_terminated());
+
+        let guard = LivenessGuard::builder()
+            .threshold(Duration::from_millis(20))
+            .thread(handle.clone())
+            .on_group_event(move |event| sender.send(event.clone()).unwrap())
+            .build();
+        assert_eq!(guard.states().len(), 1);
+        std::thread::sleep(Duration::from_millis(150));
+        // The finished thread is neither reported, nor does it make the group stalled.
+        assert!(guard.states().is_empty());
+        assert!(receiver.try_recv().is_err());
+        assert!(!handle.activity.is_observed());
+        drop(guard);
+    }
+
+    #[test]
+    fn liveness_scopes() {
+        let scope = || super::THREAD_ACTIVITY.with(|it| it.0.scope.load(Ordering::Relaxed));
+        let (sender, receiver) = channel();
+        let outer_sender = sender.clone();
+        let outer_handler = move |alive| outer_sender.send(("outer", alive)).unwrap();
+        on_liveness(Duration::from_millis(20), outer_handler, || {
+            let outer_scope = scope();
+            let inner_handler = move |alive| sender.send(("inner", alive)).unwrap();
+            on_liveness(Duration::from_secs(1), inner_handler, || {
+                // The outer scope does not apply here.
+                std::thread::sleep(Duration::from_millis(150));
+            });
+            assert_eq!(scope(), outer_scope);
+            std::thread::sleep(Duration::from_millis(100));
+        });
+        assert_eq!(scope(), 0);
+        let events = receiver.iter().collect::<Vec<_>>();
+        assert_eq!(events, vec![("outer", false)]);
+    }
+
+    #[test]
+    fn liveness_future() {
+        let (sender, receiver) = channel();
+        let mut polls = 0;
+        let line = line!() + 5;
+        let task = std::future::poll_fn(|cx| {
+            polls += 1;
+            match polls {
+                1..=3 => {
+                    crate::is_cancelled!().unwrap();
+                    if polls == 1 {
+                        // Blocking code in an async context.
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+                _ => Poll::Ready(polls),
+            }
+        });
+        let mut task = LivenessFuture::new("solver", Duration::from_millis(20), task, move |it| {
+            sender.send(it.clone()).unwrap();
+        });
+        let mut context = Context::from_waker(Waker::noop());
+        let mut task = Pin::new(&mut task);
+        assert!(task.as_mut().poll(&mut context).is_pending());
+        // The event is reported by the callback thread, which may still be sending it.
+        let stalled = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(stalled.state(), LivenessState::Stalled);
+        assert_eq!(stalled.task_name(), Some("solver"));
+        assert_eq!(stalled.thread_id(), std::thread::current().id());
+        assert_eq!(stalled.checks(), 1);
+        assert_eq!(stalled.last_check().unwrap().line(), line);
+        assert!(stalled.stalled_for() >= Duration::from_millis(20));
+        assert!(stalled.to_string().starts_with("task `solver` on thread"));
+
+        // A fast poll recovers the task, further fast polls are not reported.
+        assert!(task.as_mut().poll(&mut context).is_pending());
+        let healthy = receiver.try_recv().unwrap();
+        assert_eq!(healthy.state(), LivenessState::Healthy);
+        assert_eq!(healthy.previous(), LivenessState::Stalled);
+        assert_eq!(task.as_mut().poll(&mut context), Poll::Pending);
+        assert_eq!(task.as_mut().poll(&mut context), Poll::Ready(4));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn shared_monitor() {
+        let (sender, receiver) = channel();
+        let workers = [10u64, 30, 50].map(|millis| {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let guard = LivenessGuard::new(Duration::from_millis(millis), move |alive| {
+                    let thread = std::thread::current();
+                    sender.send((millis, alive, thread.id())).unwrap();
+                });
+                // The thread never checks cancellation, so it becomes unresponsive.
+                std::thread::sleep(Duration::from_millis(200));
+                drop(guard);
+            })
+        });
+        drop(sender);
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let reports = receiver.iter().collect::<Vec<_>>();
+        for millis in [10u64, 30, 50] {
+            assert!(reports.iter().any(|it| it.0 == millis && !it.1));
+        }
+        // Each guard invokes its callback on its own thread.
+        for (millis, _, thread) in &reports {
+            assert!(
+                reports
+                    .iter()
+                    .all(|it| (it.0 == *millis) == (it.2 == *thread))
+            );
+        }
+
+        // The callback does not run once the guard is dropped.
+        let (sender, receiver) = channel();
+        let guard = LivenessGuard::new(Duration::from_millis(10), move |alive| {
+            sender.send(alive).unwrap();
+        });
+        drop(guard);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn slow_callback() {
+        let (sender, receiver) = channel();
+        let worker = std::thread::spawn(move || {
+            let slow = LivenessGuard::new(Duration::from_millis(10), |_| {
+                std::thread::sleep(Duration::from_millis(500));
+            });
+            let fast = LivenessGuard::new(Duration::from_millis(10), move |alive| {
+                let _ = sender.send((alive, Instant::now()));
+            });
+            let start = Instant::now();
+            std::thread::sleep(Duration::from_millis(100));
+            drop(fast);
+            // Dropping the guard waits until its callback finishes.
+            drop(slow);
+            assert!(start.elapsed() >= Duration::from_millis(500));
+            start
+        });
+        let start = worker.join().unwrap();
+
+        // The slow callback does not delay the callbacks of other guards.
+        let (alive, reported) = receiver.recv().unwrap();
+        assert!(!alive);
+        assert!(reported - start < Duration::from_millis(400));
+    }
 }
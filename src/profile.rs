@@ -0,0 +1,348 @@
+use crate::CallSite;
+use crate::registration::Registration;
+use lazy_static::lazy_static;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the buckets of [`SiteProfile::histogram`]. The last bucket of
+/// the histogram counts all gaps greater than or equal to the last bound.
+pub const GAP_BUCKETS: [Duration; 7] = [
+    Duration::from_micros(1),
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+/// The number of sites listed by the [`Display`] implementation of [`ProfileReport`].
+const REPORT_SITES: usize = 10;
+
+/// Statistics of a single [`crate::is_cancelled`] call site, as returned by [`report`].
+///
+/// A gap is the time between two consecutive cancellation checks on the same thread and
+/// is attributed to the call site that ended it (i.e., the site that was reached "too late").
+#[derive(Debug, Clone)]
+pub struct SiteProfile {
+    call_site: CallSite,
+    checks: u64,
+    histogram: [u64; GAP_BUCKETS.len() + 1],
+    max_gap: Duration,
+    max_gap_from: Option<CallSite>,
+}
+
+impl SiteProfile {
+    fn new(call_site: CallSite) -> Self {
+        SiteProfile {
+            call_site,
+            checks: 0,
+            histogram: [0; GAP_BUCKETS.len() + 1],
+            max_gap: Duration::ZERO,
+            max_gap_from: None,
+        }
+    }
+
+    /// The profiled call site.
+    pub fn call_site(&self) -> &CallSite {
+        &self.call_site
+    }
+
+    /// The number of cancellation checks performed at this call site.
+    pub fn checks(&self) -> u64 {
+        self.checks
+    }
+
+    /// The number of gaps ending at this call site, bucketed by [`GAP_BUCKETS`].
+    pub fn histogram(&self) -> &[u64] {
+        &self.histogram
+    }
+
+    /// The longest gap ending at this call site.
+    pub fn max_gap(&self) -> Duration {
+        self.max_gap
+    }
+
+    /// The call site of the check that started the longest gap (see [`SiteProfile::max_gap`]).
+    pub fn max_gap_from(&self) -> Option<&CallSite> {
+        self.max_gap_from.as_ref()
+    }
+
+    fn record(&mut self, gap: Option<(Duration, &'static CallSite)>) {
+        self.checks += 1;
+        let Some((gap, from)) = gap else {
+            return;
+        };
+        let bucket = GAP_BUCKETS.iter().take_while(|it| gap >= **it).count();
+        self.histogram[bucket] += 1;
+        if gap > self.max_gap {
+            self.max_gap = gap;
+            self.max_gap_from = Some(*from);
+        }
+    }
+
+    fn merge(&mut self, other: &SiteProfile) {
+        self.checks += other.checks;
+        for (count, other) in self.histogram.iter_mut().zip(other.histogram) {
+            *count += other;
+        }
+        if other.max_gap > self.max_gap {
+            self.max_gap = other.max_gap;
+            self.max_gap_from = other.max_gap_from;
+        }
+    }
+}
+
+/// A profile of all cancellation checks in the process, as returned by [`report`].
+///
+/// The [`Display`] implementation lists the hottest call sites (where checks are possibly
+/// too dense) and the worst gaps (where checks are possibly too sparse).
+#[derive(Debug, Clone)]
+pub struct ProfileReport {
+    sites: Vec<SiteProfile>,
+}
+
+impl ProfileReport {
+    /// All profiled call sites, starting with the most frequently checked ones.
+    pub fn sites(&self) -> &[SiteProfile] {
+        &self.sites
+    }
+
+    /// The `count` most frequently checked call sites.
+    pub fn hottest(&self, count: usize) -> Vec<&SiteProfile> {
+        self.sites.iter().take(count).collect()
+    }
+
+    /// The `count` call sites with the longest gaps, starting with the longest one.
+    pub fn worst_gaps(&self, count: usize) -> Vec<&SiteProfile> {
+        let mut sites = self.sites.iter().collect::<Vec<_>>();
+        sites.sort_by_key(|it| Reverse(it.max_gap));
+        sites.truncate(count);
+        sites
+    }
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Hottest cancellation checks:")?;
+        for site in self.hottest(REPORT_SITES) {
+            writeln!(
+                f,
+                "  {:>12} checks at {} (gaps: {:?})",
+                site.checks, site.call_site, site.histogram
+            )?;
+        }
+        writeln!(f, "Worst gaps between cancellation checks:")?;
+        for site in self.worst_gaps(REPORT_SITES) {
+            match &site.max_gap_from {
+                Some(from) => writeln!(
+                    f,
+                    "  {:>12?} from {from} to {}",
+                    site.max_gap, site.call_site
+                )?,
+                None => writeln!(f, "  {:>12?} at {}", site.max_gap, site.call_site)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Collect the profile of all cancellation checks performed so far, including checks
+/// in threads that have already terminated.
+///
+/// Only checks using [`crate::is_cancelled`] without a cached trigger are profiled.
+///
+/// ```rust
+/// # use cancel_this::{Cancellable, is_cancelled, profile};
+/// let result: Cancellable<()> = cancel_this::on_atomic(Default::default(), || {
+///     for _ in 0..100 {
+///         is_cancelled!()?;
+///     }
+///     Ok(())
+/// });
+/// result.unwrap();
+///
+/// let report = profile::report();
+/// let hottest = report.hottest(1)[0];
+/// assert!(hottest.checks() >= 100);
+/// println!("{report}");
+/// ```
+pub fn report() -> ProfileReport {
+    let state = profile_state();
+    let mut sites = state.finished.clone();
+    for thread in &state.threads {
+        merge_sites(&mut sites, lock_thread(thread).sites.values());
+    }
+    drop(state);
+    let mut sites = sites.into_values().collect::<Vec<_>>();
+    sites.sort_by_key(|it| Reverse(it.checks));
+    ProfileReport { sites }
+}
+
+/// Merge the per-thread `profiles` into `sites`, keyed by the call site itself (the same
+/// call site can be stored at multiple addresses, e.g., across codegen units).
+fn merge_sites<'a>(
+    sites: &mut HashMap<CallSite, SiteProfile>,
+    profiles: impl Iterator<Item = &'a SiteProfile>,
+) {
+    for site in profiles {
+        sites
+            .entry(site.call_site)
+            .or_insert_with(|| SiteProfile::new(site.call_site))
+            .merge(site);
+    }
+}
+
+/// Print the [`report`] to the standard error output.
+pub fn dump() {
+    eprint!("{}", report());
+}
+
+/// Print the [`report`] to the standard error output once the returned value is dropped.
+/// Typically, the value is kept alive in `main`, such that the report is printed once
+/// the process exits.
+pub fn dump_on_exit() -> DumpOnExit {
+    DumpOnExit(())
+}
+
+/// Prints the [`report`] once dropped (see [`dump_on_exit`]).
+pub struct DumpOnExit(());
+
+impl Drop for DumpOnExit {
+    fn drop(&mut self) {
+        dump();
+    }
+}
+
+/// Called by [`crate::is_cancelled`] to record a cancellation check at the `call_site`.
+pub(crate) fn record(call_site: &'static CallSite) {
+    let now = Instant::now();
+    let _ = THREAD_PROFILE.try_with(|profile| {
+        let mut profile = lock_thread(&profile.0);
+        let gap = profile.last.map(|(last, from)| (now - last, from));
+        profile.last = Some((now, call_site));
+        profile
+            .sites
+            .entry(site_key(call_site))
+            .or_insert_with(|| SiteProfile::new(*call_site))
+            .record(gap);
+    });
+}
+
+/// Checks are recorded under the address of the static call site, such that the hot path
+/// does not need to hash the contents of the call site.
+fn site_key(call_site: &'static CallSite) -> usize {
+    call_site as *const CallSite as usize
+}
+
+#[derive(Default)]
+struct ThreadProfile {
+    /// The time and site of the last check in this thread.
+    last: Option<(Instant, &'static CallSite)>,
+    /// Profiles of the call sites checked in this thread (see [`site_key`]).
+    sites: HashMap<usize, SiteProfile>,
+}
+
+struct ProfileState {
+    /// Profiles of all running threads.
+    threads: Vec<Arc<Mutex<ThreadProfile>>>,
+    /// Merged profiles of all terminated threads.
+    finished: HashMap<CallSite, SiteProfile>,
+}
+
+lazy_static! {
+    /// Private global list of the profiles of all threads that check cancellation.
+    static ref PROFILE_STATE: Mutex<ProfileState> = Mutex::new(ProfileState {
+        threads: Vec::new(),
+        finished: HashMap::new(),
+    });
+}
+
+thread_local! {
+    /// The profile of this thread, registered in [`PROFILE_STATE`] while the thread is running.
+    static THREAD_PROFILE: ProfileRegistration = ProfileRegistration::register();
+}
+
+fn profile_state() -> MutexGuard<'static, ProfileState> {
+    PROFILE_STATE
+        .lock()
+        .expect("Global state of the check profiler is corrupted.")
+}
+
+fn lock_thread(profile: &Mutex<ThreadProfile>) -> MutexGuard<'_, ThreadProfile> {
+    profile
+        .lock()
+        .expect("Thread state of the check profiler is corrupted.")
+}
+
+/// The profile of a thread, merged into the finished profiles once the thread terminates.
+struct ProfileRegistration(Arc<Mutex<ThreadProfile>>, #[allow(dead_code)] Registration);
+
+impl ProfileRegistration {
+    fn register() -> Self {
+        let profile = Arc::new(Mutex::new(ThreadProfile::default()));
+        profile_state().threads.push(profile.clone());
+        let registered = profile.clone();
+        let registration = Registration::new(&PROFILE_STATE, move |mut state| {
+            state.threads.retain(|it| !Arc::ptr_eq(it, &registered));
+            if let Ok(profile) = registered.lock() {
+                merge_sites(&mut state.finished, profile.sites.values());
+            }
+        });
+        ProfileRegistration(profile, registration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profile::{GAP_BUCKETS, report};
+    use crate::{Cancellable, is_cancelled};
+    use std::time::Duration;
+
+    #[test]
+    fn profile_sites() {
+        let fast_line = line!() + 5;
+        let slow_line = line!() + 7;
+        let worker = std::thread::spawn(|| {
+            let result: Cancellable<()> = crate::on_atomic(Default::default(), || {
+                for _ in 0..10 {
+                    is_cancelled!()?;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+                is_cancelled!()
+            });
+            result.unwrap();
+        });
+        worker.join().unwrap();
+
+        // The thread has terminated, but its profile is kept.
+        let report = report();
+        let find = |line: u32| {
+            report
+                .sites()
+                .iter()
+                .find(|it| it.call_site().file() == file!() && it.call_site().line() == line)
+                .unwrap()
+        };
+        let (fast, slow) = (find(fast_line), find(slow_line));
+        assert_eq!(fast.checks(), 10);
+        assert_eq!(fast.histogram().iter().sum::<u64>(), 9);
+        assert_eq!(slow.checks(), 1);
+        assert!(slow.max_gap() >= Duration::from_millis(20));
+        assert_eq!(slow.max_gap_from(), Some(fast.call_site()));
+        // The gap falls into one of the buckets above 10ms.
+        let bucket = GAP_BUCKETS
+            .iter()
+            .position(|it| it.as_millis() == 10)
+            .unwrap();
+        assert_eq!(slow.histogram()[bucket + 1..].iter().sum::<u64>(), 1);
+
+        let worst = report.worst_gaps(usize::MAX);
+        let position = |site| worst.iter().position(|it| it.call_site() == site);
+        assert!(position(slow.call_site()) < position(fast.call_site()));
+        assert!(report.to_string().contains(&slow.call_site().to_string()));
+    }
+}
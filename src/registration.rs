@@ -0,0 +1,38 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::{Mutex, MutexGuard};
+
+/// Removes an entry from a private global state (a `static` [`Mutex`]) once dropped.
+/// Triggers typically keep the registration in an [`std::sync::Arc`], such that the entry
+/// is only removed once all copies of the trigger are dropped.
+///
+/// If the lock is poisoned, the cleanup is skipped, since panicking in drop is not a good
+/// idea and the state is unusable anyway.
+pub(crate) struct Registration(Option<Box<dyn FnOnce() + Send + Sync>>);
+
+impl Registration {
+    /// Call `unregister` with the locked `state` once the registration is dropped.
+    pub(crate) fn new<S: Send + 'static>(
+        state: &'static Mutex<S>,
+        unregister: impl FnOnce(MutexGuard<'static, S>) + Send + Sync + 'static,
+    ) -> Self {
+        Registration(Some(Box::new(move || {
+            if let Ok(state) = state.lock() {
+                unregister(state);
+            }
+        })))
+    }
+}
+
+impl Debug for Registration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Registration")
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(unregister) = self.0.take() {
+            unregister();
+        }
+    }
+}
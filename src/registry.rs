@@ -0,0 +1,325 @@
+use crate::registration::Registration;
+use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerListener};
+use log::trace;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+
+/// The [`crate::Cancelled`] cause reported when a scope is canceled using [`cancel_by_id`].
+pub const REGISTRY_CAUSE: &str = "CancelById";
+
+thread_local! {
+    /// Incremented every time [`crate::is_cancelled`] is checked in this thread.
+    static CHECK_STAMP: Arc<AtomicU64> = Arc::new(AtomicU64::default());
+}
+
+/// Information about a single active cancellation scope, as returned by [`snapshot`].
+#[derive(Debug, Clone)]
+pub struct ScopeInfo {
+    id: u64,
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+    label: Option<String>,
+    triggers: Vec<&'static str>,
+    started: Instant,
+    check_stamp: u64,
+    is_cancelled: bool,
+}
+
+impl ScopeInfo {
+    /// The unique id of the scope, which can be used with [`cancel_by_id`].
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The id of the thread that entered the scope.
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// The name of the thread that entered the scope (if the thread is named).
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+
+    /// The label of the scope (see [`crate::on_labeled_trigger`]).
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Type names of all triggers active in the scope, starting with the outermost one
+    /// (i.e., the last item is the trigger that created this scope).
+    pub fn triggers(&self) -> &[&'static str] {
+        self.triggers.as_slice()
+    }
+
+    /// The moment the scope was entered.
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// The time elapsed since the scope was entered.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The number of cancellation checks performed by the thread when the snapshot was taken.
+    /// If the stamp does not change between two snapshots, the thread did not check for
+    /// cancellation in the meantime.
+    ///
+    /// Only checks of the thread-local triggers are counted (i.e., [`crate::is_cancelled`]
+    /// without a cached trigger).
+    pub fn check_stamp(&self) -> u64 {
+        self.check_stamp
+    }
+
+    /// True if the scope was canceled using [`cancel_by_id`] when the snapshot was taken.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled
+    }
+}
+
+/// Get information about all cancellation scopes that are currently active in this process.
+///
+/// ```rust
+/// # use cancel_this::{Cancellable, CancelNever, is_cancelled, registry};
+/// let result: Cancellable<()> = cancel_this::on_labeled_trigger("import", CancelNever, || {
+///     let scopes = registry::snapshot();
+///     let scope = scopes.iter().find(|it| it.label() == Some("import")).unwrap();
+///     assert_eq!(scope.triggers(), &["CancelNever"]);
+///
+///     // Cancel the scope in which we are currently running.
+///     assert!(registry::cancel_by_id(scope.id()));
+///     is_cancelled!()
+/// });
+/// assert_eq!(result.unwrap_err().cause(), registry::REGISTRY_CAUSE);
+/// ```
+pub fn snapshot() -> Vec<ScopeInfo> {
+    let state = registry_state();
+    state
+        .scopes
+        .iter()
+        .map(|scope| ScopeInfo {
+            id: scope.id,
+            thread_id: scope.thread_id,
+            thread_name: scope.thread_name.clone(),
+            label: scope.label.clone(),
+            triggers: scope.triggers.clone(),
+            started: scope.started,
+            check_stamp: scope.check_stamp.load(Ordering::SeqCst),
+            is_cancelled: scope.trigger.is_cancelled(),
+        })
+        .collect()
+}
+
+/// Cancel the active scope with the given `id` (see [`ScopeInfo::id`]). Canceling a scope
+/// also cancels all scopes nested in it. Returns `false` if no such scope is active.
+pub fn cancel_by_id(id: u64) -> bool {
+    let state = registry_state();
+    match state.scopes.iter().find(|it| it.id == id) {
+        None => false,
+        Some(scope) => {
+            trace!("Cancelling scope {id} through the registry.");
+            scope.trigger.cancel();
+            true
+        }
+    }
+}
+
+/// Called by [`crate::is_cancelled`] to record the cancellation check.
+pub(crate) fn record_check() {
+    let _ = CHECK_STAMP.try_with(|it| it.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Register a new scope with the given `label` and trigger type names. The returned trigger
+/// must be pushed into the chain together with the scope trigger, and the scope is
+/// unregistered once the returned registration is dropped.
+pub(crate) fn enter<T: CancellationTrigger + 'static>(
+    label: Option<String>,
+    triggers: Vec<&'static str>,
+    trigger: T,
+) -> (RegisteredTrigger<T>, Registration) {
+    let cancel = CancelAtomic::default();
+    let current = std::thread::current();
+    let mut state = registry_state();
+    let id = state.next_id;
+    state.next_id += 1;
+    state.scopes.push(ScopeRecord {
+        id,
+        thread_id: current.id(),
+        thread_name: current.name().map(|it| it.to_string()),
+        label,
+        triggers,
+        started: Instant::now(),
+        check_stamp: CHECK_STAMP.with(Arc::clone),
+        trigger: cancel.clone(),
+    });
+    let trigger = RegisteredTrigger {
+        id,
+        inner: trigger,
+        cancel,
+    };
+    let registration = Registration::new(&REGISTRY_STATE, move |mut state| {
+        state.scopes.retain(|it| it.id != id);
+    });
+    (trigger, registration)
+}
+
+/// A scope trigger that can be also canceled through [`cancel_by_id`].
+pub(crate) struct RegisteredTrigger<T> {
+    id: u64,
+    inner: T,
+    cancel: CancelAtomic,
+}
+
+impl<T: CancellationTrigger> Clone for RegisteredTrigger<T> {
+    fn clone(&self) -> Self {
+        RegisteredTrigger {
+            id: self.id,
+            inner: dyn_clone::clone(&self.inner),
+            cancel: self.cancel.clone(),
+        }
+    }
+}
+
+impl<T: CancellationTrigger> CancellationTrigger for RegisteredTrigger<T> {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled() || self.inner.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        if self.cancel.is_cancelled() {
+            REGISTRY_CAUSE
+        } else {
+            self.inner.type_name()
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        if self.cancel.is_cancelled() {
+            Some(format!("scope {} canceled by id", self.id))
+        } else {
+            self.inner.detail()
+        }
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        let is_cancel_subscribed = self.cancel.subscribe(listener);
+        self.inner.subscribe(listener) && is_cancel_subscribed
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.cancel.unsubscribe(listener);
+        self.inner.unsubscribe(listener);
+    }
+
+    fn check(&self) -> Option<Cancelled> {
+        if self.cancel.is_cancelled() {
+            Some(Cancelled::with_detail(
+                REGISTRY_CAUSE,
+                format!("scope {} canceled by id", self.id),
+            ))
+        } else {
+            self.inner.check()
+        }
+    }
+}
+
+struct ScopeRecord {
+    id: u64,
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+    label: Option<String>,
+    triggers: Vec<&'static str>,
+    started: Instant,
+    check_stamp: Arc<AtomicU64>,
+    trigger: CancelAtomic,
+}
+
+struct RegistryState {
+    next_id: u64,
+    scopes: Vec<ScopeRecord>,
+}
+
+/// Private global registry of all active scopes.
+static REGISTRY_STATE: Mutex<RegistryState> = Mutex::new(RegistryState {
+    next_id: 0,
+    scopes: Vec::new(),
+});
+
+fn registry_state() -> MutexGuard<'static, RegistryState> {
+    REGISTRY_STATE
+        .lock()
+        .expect("Global state of the scope registry is corrupted.")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registry::{REGISTRY_CAUSE, cancel_by_id, snapshot};
+    use crate::{CancelAtomic, CancelNever, Cancellable, is_cancelled};
+    use std::time::Duration;
+
+    #[test]
+    fn registry_scopes() {
+        let handle = std::thread::Builder::new()
+            .name("registry-worker".to_string())
+            .spawn(|| {
+                let outer = CancelAtomic::new();
+                let result: Cancellable<()> =
+                    crate::on_labeled_trigger("outer", outer.clone(), || {
+                        crate::on_trigger(CancelNever, || {
+                            for _ in 0..500 {
+                                is_cancelled!()?;
+                                std::thread::sleep(Duration::from_millis(10));
+                            }
+                            Ok(())
+                        })
+                    });
+                result
+            })
+            .unwrap();
+
+        std::thread::sleep(Duration::from_millis(50));
+        let scopes = snapshot();
+        let outer = scopes
+            .iter()
+            .find(|it| it.label() == Some("outer"))
+            .unwrap();
+        assert_eq!(outer.thread_name(), Some("registry-worker"));
+        assert_eq!(outer.triggers(), &["CancelAtomic"]);
+        assert!(outer.elapsed() >= Duration::from_millis(50));
+        let inner = scopes
+            .iter()
+            .find(|it| it.thread_id() == outer.thread_id() && it.label().is_none())
+            .unwrap();
+        assert_eq!(inner.triggers(), &["CancelAtomic", "CancelNever"]);
+        assert!(!inner.is_cancelled());
+
+        // The check stamp increases while the thread is running.
+        std::thread::sleep(Duration::from_millis(50));
+        let stamp = snapshot()
+            .into_iter()
+            .find(|it| it.id() == inner.id())
+            .unwrap()
+            .check_stamp();
+        assert!(stamp > inner.check_stamp());
+
+        // Cancelling the outer scope also cancels the inner scope.
+        assert!(cancel_by_id(outer.id()));
+        let error = handle.join().unwrap().unwrap_err();
+        assert_eq!(error.cause(), REGISTRY_CAUSE);
+        assert_eq!(
+            error.detail(),
+            Some(format!("scope {} canceled by id", outer.id()).as_str())
+        );
+
+        // Once exited, the scopes are removed.
+        assert!(!cancel_by_id(outer.id()));
+        assert!(
+            snapshot()
+                .iter()
+                .all(|it| it.thread_id() != outer.thread_id())
+        );
+    }
+}
@@ -1,7 +1,7 @@
-use crate::{CancellationTrigger, Cancelled};
+use crate::{CancellationTrigger, Cancelled, TriggerListener};
 use log::trace;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
 /// Run the given `action`, cancelling it if the provided [`CancelAtomic`] `trigger` is canceled
 /// by some external mechanism.
@@ -56,21 +56,43 @@ where
 /// [`CancelAtomic::cancel`]. See also [`on_atomic`].
 ///
 /// It is safe to cancel this trigger multiple times, and once canceled, the trigger
-/// cannot be reset.
+/// cannot be reset. The trigger supports subscriptions (see [`CancellationTrigger::subscribe`]).
 ///
 /// ## Logging
 ///  - `[trace]` Every time the trigger is canceled.
 #[derive(Debug, Clone, Default)]
-pub struct CancelAtomic(Arc<AtomicBool>);
+pub struct CancelAtomic(Arc<AtomicCore>);
+
+#[derive(Debug, Default)]
+struct AtomicCore {
+    is_cancelled: AtomicBool,
+    listeners: Mutex<Vec<TriggerListener>>,
+}
 
 impl CancellationTrigger for CancelAtomic {
     fn is_cancelled(&self) -> bool {
-        self.0.load(Ordering::SeqCst)
+        self.0.is_cancelled.load(Ordering::SeqCst)
     }
 
     fn type_name(&self) -> &'static str {
         "CancelAtomic"
     }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.listeners().push(listener.clone());
+        // If the trigger was canceled before the listener was added, it would not be notified.
+        if self.is_cancelled() {
+            listener.notify();
+        }
+        true
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        let mut listeners = self.listeners();
+        if let Some(index) = listeners.iter().position(|it| it.same_as(listener)) {
+            listeners.swap_remove(index);
+        }
+    }
 }
 
 impl CancelAtomic {
@@ -86,18 +108,42 @@ impl CancelAtomic {
     pub fn cancel(&self) {
         let first_caller = self
             .0
+            .is_cancelled
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
             .is_ok();
         if first_caller {
             trace!("`CancelAtomic[{:p}]` cancelled.", self.id_ref());
+            for listener in self.listeners().iter() {
+                listener.notify();
+            }
         } else {
             // The atomic swap can only fail if the value is already `true`.
             trace!("`CancelAtomic[{:p}]` already cancelled.", self.id_ref());
         }
     }
 
+    /// Reset the cancellation, returning `true` if the trigger was canceled. This is only
+    /// used by triggers which are explicitly resettable (see [`crate::clear_interrupt`]).
+    pub(crate) fn reset(&self) -> bool {
+        self.0.is_cancelled.swap(false, Ordering::SeqCst)
+    }
+
+    /// True if this trigger and `other` are copies of the same trigger.
+    pub(crate) fn same_as(&self, other: &CancelAtomic) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
     /// Provides a reference which "identifies" this trigger when logging.
     pub(crate) fn id_ref(&self) -> &AtomicBool {
-        self.0.as_ref()
+        &self.0.is_cancelled
+    }
+
+    fn listeners(&self) -> MutexGuard<'_, Vec<TriggerListener>> {
+        // The list of listeners cannot be left in an inconsistent state, so we can simply
+        // ignore poisoning.
+        self.0
+            .listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
     }
 }
@@ -1,4 +1,11 @@
-use crate::{CancelNever, CancellationTrigger, DynamicCancellationTrigger};
+use crate::{
+    CancelNever, CancellationTrigger, Cancelled, DynamicCancellationTrigger, TriggerListener,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Source of unique ids of chain entries. Ids are increasing, meaning triggers that are
+/// pushed later always have a greater id (see [`CancelChain::check_after`]).
+static NEXT_ENTRY_ID: AtomicU64 = AtomicU64::new(1);
 
 /// Implementation of [`CancellationTrigger`] which chains together several
 /// trigger implementations.
@@ -6,44 +13,239 @@ use crate::{CancelNever, CancellationTrigger, DynamicCancellationTrigger};
 /// This is mostly used internally by [`crate::on_trigger`] to implement chaining of
 /// multiple cancellation scopes. However, it is still a normal [`CancellationTrigger`] and
 /// thus can be used to combine triggers manually as well.
-#[derive(Clone, Default)]
-pub struct CancelChain(Vec<DynamicCancellationTrigger>);
+///
+/// The chain subscribes to all triggers that support it (see [`CancellationTrigger::subscribe`]).
+/// As long as all triggers in the chain are subscribed and none of them has been canceled,
+/// checking the chain is a single atomic load. Only once a trigger is canceled (or if the chain
+/// contains triggers that must be checked explicitly), the individual triggers are checked.
+#[derive(Default)]
+pub struct CancelChain {
+    triggers: Vec<ChainEntry>,
+    listener: TriggerListener,
+    /// The number of triggers that do not support subscriptions.
+    unsubscribed: usize,
+}
+
+struct ChainEntry {
+    id: u64,
+    trigger: DynamicCancellationTrigger,
+    /// The type name of the trigger, recorded once the trigger is pushed
+    /// (see [`CancelChain::type_names`]).
+    type_name: &'static str,
+    is_subscribed: bool,
+}
 
 impl CancellationTrigger for CancelChain {
     fn is_cancelled(&self) -> bool {
+        if self.is_quiet() {
+            return false;
+        }
         // Should not really matter, but start checking from the "innermost" condition.
-        self.0.iter().rev().any(|t| t.is_cancelled())
+        self.iter().any(|t| t.is_cancelled())
     }
 
     fn type_name(&self) -> &'static str {
-        self.0
-            .iter()
-            .rev()
+        self.iter()
             .find(|t| t.is_cancelled())
             .map(|it| it.type_name())
             .unwrap_or("CancelChain")
     }
+
+    fn detail(&self) -> Option<String> {
+        self.iter()
+            .find(|t| t.is_cancelled())
+            .and_then(|it| it.detail())
+    }
+
+    fn check(&self) -> Option<Cancelled> {
+        if self.is_quiet() {
+            return None;
+        }
+        self.iter().find_map(|t| t.check())
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        // All triggers have to be subscribed, even if some of them do not support it.
+        let mut is_subscribed = true;
+        for entry in &self.triggers {
+            is_subscribed &= entry.trigger.subscribe(listener);
+        }
+        is_subscribed
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        for entry in &self.triggers {
+            entry.trigger.unsubscribe(listener);
+        }
+    }
+}
+
+impl Clone for CancelChain {
+    fn clone(&self) -> Self {
+        // The copy needs its own listener, because the subscriptions of this chain are
+        // removed once its triggers are popped (or the chain is dropped).
+        self.clone_with_listener(TriggerListener::default())
+    }
+}
+
+impl Drop for CancelChain {
+    fn drop(&mut self) {
+        for entry in &self.triggers {
+            entry.trigger.unsubscribe(&self.listener);
+        }
+    }
 }
 
 impl CancelChain {
+    /// Create an empty chain which notifies the given `listener` once any of its triggers
+    /// is canceled (or once a trigger that does not support subscriptions is pushed).
+    pub(crate) fn with_listener(listener: TriggerListener) -> Self {
+        CancelChain {
+            triggers: Vec::new(),
+            listener,
+            unsubscribed: 0,
+        }
+    }
+
+    /// Make a copy of this chain which uses the given `listener`. Unlike [`Clone::clone`],
+    /// the copy can share the listener with this chain, since each subscription is removed
+    /// separately (see [`CancellationTrigger::unsubscribe`]).
+    pub(crate) fn clone_with_listener(&self, listener: TriggerListener) -> Self {
+        let mut chain = CancelChain::with_listener(listener);
+        for entry in &self.triggers {
+            chain.push_entry(entry.id, entry.type_name, entry.trigger.clone());
+        }
+        chain
+    }
+
     /// Remove the first trigger in the chain.
     pub fn pop(&mut self) -> Option<DynamicCancellationTrigger> {
-        self.0.pop()
+        let trigger = self.remove()?;
+        // The removed trigger could have been the one that notified the listener.
+        self.refresh();
+        Some(trigger)
+    }
+
+    /// Remove the first trigger in the chain without calling [`CancelChain::refresh`].
+    /// The caller is responsible for refreshing the chain.
+    pub(crate) fn remove(&mut self) -> Option<DynamicCancellationTrigger> {
+        let entry = self.triggers.pop()?;
+        entry.trigger.unsubscribe(&self.listener);
+        if !entry.is_subscribed {
+            self.unsubscribed -= 1;
+        }
+        Some(entry.trigger)
     }
 
     /// Add a new cancellation trigger. The new chain starts with the given trigger
     /// and continues with the already present ones.
     pub fn push<T: CancellationTrigger + 'static>(&mut self, trigger: T) {
-        self.0.push(Box::new(trigger));
+        self.push_named(trigger.type_name(), trigger);
+    }
+
+    /// The same as [`CancelChain::push`], but the trigger is listed under the given `type_name`
+    /// (see [`CancelChain::type_names`]).
+    pub(crate) fn push_named<T: CancellationTrigger + 'static>(
+        &mut self,
+        type_name: &'static str,
+        trigger: T,
+    ) {
+        let id = NEXT_ENTRY_ID.fetch_add(1, Ordering::Relaxed);
+        self.push_entry(id, type_name, Box::new(trigger));
+    }
+
+    fn push_entry(
+        &mut self,
+        id: u64,
+        type_name: &'static str,
+        trigger: DynamicCancellationTrigger,
+    ) {
+        let is_subscribed = trigger.subscribe(&self.listener);
+        if !is_subscribed {
+            // The listener cannot tell whether the trigger is canceled.
+            self.unsubscribed += 1;
+            self.listener.notify();
+        }
+        self.triggers.push(ChainEntry {
+            id,
+            trigger,
+            type_name,
+            is_subscribed,
+        });
+    }
+
+    /// Reset the listener if none of the subscribed triggers is canceled anymore (i.e.,
+    /// the canceled trigger was removed, or its cancellation was reset).
+    pub(crate) fn refresh(&self) {
+        self.refresh_with(|| false);
+    }
+
+    /// The same as [`CancelChain::refresh`], but the listener also stays notified
+    /// if `is_notified` is true, i.e., if the listener is shared with other notifications.
+    pub(crate) fn refresh_with(&self, is_notified: impl FnOnce() -> bool) {
+        // The listener must be reset before the triggers are checked, otherwise
+        // a cancellation happening in between could be lost.
+        self.listener.reset();
+        let is_cancelled = self.unsubscribed > 0
+            || is_notified()
+            || self
+                .triggers
+                .iter()
+                .any(|it| it.is_subscribed && it.trigger.is_cancelled());
+        if is_cancelled {
+            self.listener.notify();
+        }
+    }
+
+    /// True if the chain is certainly not canceled without checking the individual triggers.
+    fn is_quiet(&self) -> bool {
+        self.unsubscribed == 0 && !self.listener.is_notified()
+    }
+
+    /// The listener notified once any of the triggers is canceled.
+    pub(crate) fn listener(&self) -> &TriggerListener {
+        &self.listener
+    }
+
+    /// True if all triggers in the chain support subscriptions.
+    pub(crate) fn is_subscribed(&self) -> bool {
+        self.unsubscribed == 0
+    }
+
+    /// The id of the innermost trigger (if any).
+    pub(crate) fn last_id(&self) -> Option<u64> {
+        self.triggers.last().map(|it| it.id)
+    }
+
+    /// Check only the triggers that were pushed after the trigger with the given `id`
+    /// (see [`CancelChain::last_id`]).
+    pub(crate) fn check_after(&self, id: u64) -> Option<Cancelled> {
+        self.triggers
+            .iter()
+            .rev()
+            .take_while(|it| it.id > id)
+            .find_map(|it| it.trigger.check())
+    }
+
+    /// Iterate the triggers, starting with the innermost one.
+    fn iter(&self) -> impl Iterator<Item = &DynamicCancellationTrigger> {
+        self.triggers.iter().rev().map(|it| &it.trigger)
+    }
+
+    /// Type names of all triggers in the chain, starting with the outermost one.
+    ///
+    /// The names are recorded once the triggers are pushed, so no trigger code is executed.
+    pub(crate) fn type_names(&self) -> Vec<&'static str> {
+        self.triggers.iter().map(|it| it.type_name).collect()
     }
 
     /// Make a copy of this trigger chain, but if the chain is empty or only has a single element,
     /// replace it with a simplified trigger which does not need vector traversal.
     pub fn clone_and_flatten(&self) -> DynamicCancellationTrigger {
-        if self.0.is_empty() {
+        if self.triggers.is_empty() {
             Box::new(CancelNever)
-        } else if self.0.len() == 1 {
-            self.0[0].clone()
+        } else if self.triggers.len() == 1 {
+            self.triggers[0].trigger.clone()
         } else {
             Box::new(self.clone())
         }
@@ -53,8 +255,68 @@ impl CancelChain {
 #[cfg(test)]
 mod tests {
     use crate::{CancelAtomic, CancelChain, CancelTimer, CancellationTrigger};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::Duration;
 
+    /// A canceled trigger which counts how many times it was evaluated.
+    #[derive(Clone, Default)]
+    struct CountingTrigger(Arc<AtomicUsize>);
+
+    impl CancellationTrigger for CountingTrigger {
+        fn is_cancelled(&self) -> bool {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+
+        fn type_name(&self) -> &'static str {
+            "CountingTrigger"
+        }
+    }
+
+    #[test]
+    fn chain_subscriptions() {
+        let outer = CancelAtomic::new();
+        let inner = CancelAtomic::new();
+        let mut chain = CancelChain::default();
+        chain.push(outer.clone());
+        chain.push(inner.clone());
+        assert!(chain.is_quiet());
+
+        // Copies of the chain are subscribed independently.
+        let copy = chain.clone();
+        inner.cancel();
+        assert!(!chain.is_quiet());
+        assert_eq!(chain.check().unwrap().cause(), "CancelAtomic");
+        assert!(copy.is_cancelled());
+
+        // Once the canceled trigger is removed, the chain is quiet again.
+        chain.pop();
+        assert!(chain.is_quiet());
+        assert!(!chain.is_cancelled());
+        outer.cancel();
+        assert!(chain.is_cancelled());
+        drop(copy);
+
+        // Triggers without subscriptions are always checked.
+        let mut chain = CancelChain::default();
+        chain.push(CountingTrigger::default());
+        assert!(!chain.is_quiet());
+        assert!(chain.is_cancelled());
+    }
+
+    #[test]
+    fn chain_single_check() {
+        let counter = CountingTrigger::default();
+        let mut chain = CancelChain::default();
+        chain.push(counter.clone());
+        chain.push(CancelAtomic::new());
+
+        let error = crate::check_cancellation(&chain).unwrap_err();
+        assert_eq!(error.cause(), "CountingTrigger");
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn chain_flattening() {
         // Empty chain flattens to cancel never.
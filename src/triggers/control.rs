@@ -0,0 +1,619 @@
+use crate::registration::Registration;
+use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerListener};
+use lazy_static::lazy_static;
+use log::{debug, trace, warn};
+use std::io::{BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Run the given `action` as a named scope, cancelling it using [`CancelControl`] once
+/// the `cancel <id>` command is received by a [`ControlServer`].
+///
+/// ```rust
+/// # use std::io::{BufRead, BufReader, Write};
+/// # use std::os::unix::net::UnixStream;
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, ControlServer, is_cancelled};
+/// # let _ = env_logger::builder().is_test(true).try_init();
+/// fn cancellable_counter(count: usize) -> Result<(), Cancelled> {
+///     for _ in 0..count {
+///         is_cancelled!()?;
+///         std::thread::sleep(Duration::from_millis(10));
+///     }
+///     Ok(())
+/// }
+///
+/// let path = std::env::temp_dir().join(format!("cancel-this-doc-{}.sock", std::process::id()));
+/// let server = ControlServer::bind(&path).unwrap();
+///
+/// std::thread::spawn(move || {
+///     // Wait for 100ms, then find the scope and cancel it.
+///     std::thread::sleep(Duration::from_millis(100));
+///     let mut stream = UnixStream::connect(path).unwrap();
+///     let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+///     writeln!(stream, "list").unwrap();
+///     let scope = lines.next().unwrap().unwrap();
+///     let id = scope.split('\t').next().unwrap();
+///     writeln!(stream, "cancel {id}").unwrap();
+/// });
+///
+/// let result = cancel_this::on_control("analysis", || cancellable_counter(500));
+/// assert_eq!(result.unwrap_err().cause(), "CancelControl");
+/// ```
+pub fn on_control<TResult, TError, TAction>(name: &str, action: TAction) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(CancelControl::new(name), action)
+}
+
+/// Implementation of [`CancellationTrigger`] representing a named scope that can be canceled
+/// from outside the process through a [`ControlServer`].
+///
+/// Each trigger is assigned a unique id upon creation and stays listed by the server until
+/// all copies of the trigger are dropped. Optionally, the scope can have a deadline
+/// ([`CancelControl::with_timeout`]), which can be prolonged using the `extend` command.
+/// Checking a trigger without a deadline is as cheap as [`CancelAtomic`]; with a deadline,
+/// every check also reads the current time.
+///
+/// See also [`on_control`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled.
+#[derive(Debug, Clone)]
+pub struct CancelControl {
+    scope: Arc<ControlScope>,
+    // The registration is only needed to unregister the scope once all copies are dropped.
+    #[allow(dead_code)]
+    registration: Arc<Registration>,
+}
+
+impl CancellationTrigger for CancelControl {
+    fn is_cancelled(&self) -> bool {
+        self.scope.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelControl"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.scope.deadline.is_none() && self.scope.trigger.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.scope.trigger.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.scope.detail.get().cloned()
+    }
+}
+
+impl CancelControl {
+    /// Create a new named scope without a deadline.
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self::register(name.into(), None)
+    }
+
+    /// Create a new named scope that is also canceled once the given `timeout` elapses
+    /// (unless it is prolonged using the `extend` command).
+    pub fn with_timeout<T: Into<String>>(name: T, timeout: Duration) -> Self {
+        Self::register(name.into(), Some(timeout))
+    }
+
+    /// The unique id of this scope, used by the control commands.
+    pub fn id(&self) -> u64 {
+        self.scope.id
+    }
+
+    /// The name of this scope.
+    pub fn name(&self) -> &str {
+        self.scope.name.as_str()
+    }
+
+    /// Cancel the scope directly, the same way as the `cancel` command.
+    pub fn cancel(&self) {
+        self.scope.cancel("canceled via control socket".to_string());
+    }
+
+    /// Prolong the deadline of this scope by `duration`, the same way as the `extend` command.
+    /// Returns `false` if the scope has no deadline or it is already canceled.
+    pub fn extend(&self, duration: Duration) -> bool {
+        self.scope.extend(duration)
+    }
+
+    fn register(name: String, timeout: Option<Duration>) -> Self {
+        let mut state = control_state();
+        let id = state.next_id;
+        state.next_id += 1;
+        let scope = Arc::new(ControlScope {
+            id,
+            name,
+            trigger: CancelAtomic::default(),
+            deadline: timeout.map(|it| {
+                let timeout = u64::try_from(it.as_nanos()).unwrap_or(u64::MAX);
+                AtomicU64::new(since_epoch(Instant::now()).saturating_add(timeout))
+            }),
+            detail: OnceLock::new(),
+        });
+        state.scopes.push(scope.clone());
+        CancelControl {
+            scope,
+            registration: Arc::new(Registration::new(&CONTROL_STATE, move |mut state| {
+                state.scopes.retain(|it| it.id != id);
+            })),
+        }
+    }
+}
+
+/// The state shared by all copies of a [`CancelControl`] trigger and the global registry.
+#[derive(Debug)]
+struct ControlScope {
+    id: u64,
+    name: String,
+    trigger: CancelAtomic,
+    /// Nanoseconds since [`CONTROL_EPOCH`].
+    deadline: Option<AtomicU64>,
+    detail: OnceLock<String>,
+}
+
+impl ControlScope {
+    fn is_cancelled(&self) -> bool {
+        if self.trigger.is_cancelled() {
+            return true;
+        }
+        let Some(deadline) = self.deadline.as_ref() else {
+            return false;
+        };
+        if since_epoch(Instant::now()) < deadline.load(Ordering::SeqCst) {
+            return false;
+        }
+        self.cancel("deadline elapsed".to_string());
+        true
+    }
+
+    fn cancel(&self, detail: String) {
+        if self.detail.set(detail).is_ok() {
+            trace!(
+                "`CancelControl[{:p}]` canceled (scope {} `{}`).",
+                self.trigger.id_ref(),
+                self.id,
+                self.name
+            );
+        }
+        self.trigger.cancel();
+    }
+
+    fn extend(&self, duration: Duration) -> bool {
+        let Some(deadline) = self.deadline.as_ref() else {
+            return false;
+        };
+        if self.is_cancelled() {
+            return false;
+        }
+        let extension = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let _ = deadline.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |it| {
+            Some(it.saturating_add(extension))
+        });
+        // The deadline could have elapsed in the meantime.
+        !self.is_cancelled()
+    }
+
+    /// The time until the deadline, if any.
+    fn remaining(&self) -> Option<Duration> {
+        let deadline = self.deadline.as_ref()?.load(Ordering::SeqCst);
+        let now = since_epoch(Instant::now());
+        Some(Duration::from_nanos(deadline.saturating_sub(now)))
+    }
+}
+
+lazy_static! {
+    /// The reference point for the deadlines of [`CancelControl`] triggers.
+    static ref CONTROL_EPOCH: Instant = Instant::now();
+}
+
+fn since_epoch(instant: Instant) -> u64 {
+    let nanos = instant.saturating_duration_since(*CONTROL_EPOCH).as_nanos();
+    u64::try_from(nanos).unwrap_or(u64::MAX)
+}
+
+struct ControlState {
+    next_id: u64,
+    scopes: Vec<Arc<ControlScope>>,
+}
+
+/// Private global registry of all active [`CancelControl`] scopes.
+static CONTROL_STATE: Mutex<ControlState> = Mutex::new(ControlState {
+    next_id: 0,
+    scopes: Vec::new(),
+});
+
+fn control_state() -> MutexGuard<'static, ControlState> {
+    CONTROL_STATE
+        .lock()
+        .expect("Global state of `CancelControl` is corrupted.")
+}
+
+/// Listens on a Unix domain socket and executes commands that control the active
+/// [`CancelControl`] scopes. The server runs on a background thread until it is dropped,
+/// at which point the socket file is removed (unless it was replaced in the meantime).
+///
+/// The protocol is line-based. Each command is answered by zero or more data lines, followed
+/// by a single `ok` line, or by a single `error: <message>` line:
+///  - `list`: One line per active scope with tab-separated id, name, and status (`active`,
+///    `active <remaining>ms`, or `cancelled`).
+///  - `cancel <id>`: Cancel the scope with the given id.
+///  - `extend <id> <duration>`: Prolong the deadline of the given scope. The duration is
+///    a whole number with an optional unit (`ms`, `s`, `m` or `h`; seconds by default).
+///
+/// Each connection is served by its own thread, but at most eight connections are open
+/// at the same time. Further connections are answered with
+/// `error: too many connections` and closed.
+///
+/// ## Logging
+///  - `[debug]` Every time a command is executed.
+///  - `[warn]` If a connection cannot be accepted or served.
+#[derive(Debug)]
+pub struct ControlServer {
+    path: PathBuf,
+    /// The device and inode of the socket file, such that a replaced file is not removed.
+    file_id: Option<(u64, u64)>,
+    listener: UnixListener,
+    stop: Arc<AtomicBool>,
+    /// Disconnected once the listener thread terminates.
+    finished: Receiver<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// Start a new server listening on the given `path`. Fails if the socket cannot be created
+    /// (e.g., the path already exists).
+    pub fn bind<T: AsRef<Path>>(path: T) -> Result<Self, std::io::Error> {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path)?;
+        let file_id = file_id(&path);
+        let thread_listener = match listener.try_clone() {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let (finished_sender, finished) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("cancel-this-control".to_string())
+            .spawn(move || {
+                serve(thread_listener, thread_stop);
+                drop(finished_sender);
+            });
+        match thread {
+            Ok(thread) => Ok(ControlServer {
+                path,
+                file_id,
+                listener,
+                stop,
+                finished,
+                thread: Some(thread),
+            }),
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                Err(e)
+            }
+        }
+    }
+
+    /// The path of the socket.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake up the listener thread, which is blocked in `accept`. Shutting down the socket
+        // works even if the socket file was removed or replaced. Connecting to the socket is
+        // a fallback for platforms where `shutdown` does not interrupt `accept`.
+        unsafe { libc::shutdown(self.listener.as_raw_fd(), libc::SHUT_RDWR) };
+        let _ = UnixStream::connect(&self.path);
+        match self.finished.recv_timeout(STOP_TIMEOUT) {
+            Err(RecvTimeoutError::Timeout) => {
+                warn!("Control server thread cannot be stopped. Possible thread leak.");
+            }
+            _ => {
+                if let Some(thread) = self.thread.take()
+                    && thread.join().is_err()
+                {
+                    warn!("Control server thread panicked.");
+                }
+            }
+        }
+        if self.file_id.is_some() && file_id(&self.path) == self.file_id {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// How long [`ControlServer`] waits for the listener thread to stop once dropped.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The maximal number of connections that a [`ControlServer`] serves at the same time.
+const MAX_CONNECTIONS: usize = 8;
+
+/// The device and inode of the given file (if it exists).
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+fn serve(listener: UnixListener, stop: Arc<AtomicBool>) {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Control connection cannot be accepted: {e:?}");
+                continue;
+            }
+        };
+        // Only the listener thread increments the counter, so the limit cannot be exceeded.
+        if open.load(Ordering::SeqCst) >= MAX_CONNECTIONS {
+            warn!("Control connection rejected: too many connections.");
+            let _ = stream.write_all(b"error: too many connections\n");
+            continue;
+        }
+        open.fetch_add(1, Ordering::SeqCst);
+        let thread_open = open.clone();
+        let spawned = std::thread::Builder::new()
+            .name("cancel-this-control-connection".to_string())
+            .spawn(move || {
+                if let Err(e) = serve_connection(stream) {
+                    warn!("Control connection failed: {e:?}");
+                }
+                thread_open.fetch_sub(1, Ordering::SeqCst);
+            });
+        if let Err(e) = spawned {
+            open.fetch_sub(1, Ordering::SeqCst);
+            warn!("Control connection cannot be served: {e:?}");
+        }
+    }
+}
+
+fn serve_connection(stream: UnixStream) -> Result<(), std::io::Error> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = execute(line.trim());
+        writer.write_all(response.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Execute a single command of the control protocol, returning the full response.
+fn execute(command: &str) -> String {
+    debug!("Executing control command `{command}`.");
+    let words = command.split_whitespace().collect::<Vec<_>>();
+    let result = match words.as_slice() {
+        ["list"] => Ok(list_scopes()),
+        ["cancel", id] => find_scope(id).map(|scope| {
+            scope.cancel("canceled via control socket".to_string());
+            String::new()
+        }),
+        ["extend", id, duration] => find_scope(id).and_then(|scope| {
+            let duration =
+                parse_duration(duration).ok_or_else(|| format!("invalid duration `{duration}`"))?;
+            if scope.extend(duration) {
+                Ok(String::new())
+            } else {
+                Err(format!("scope {} has no active deadline", scope.id))
+            }
+        }),
+        _ => Err(format!("unknown command `{command}`")),
+    };
+    match result {
+        Ok(data) => format!("{data}ok\n"),
+        Err(message) => format!("error: {message}\n"),
+    }
+}
+
+fn list_scopes() -> String {
+    let scopes = control_state().scopes.clone();
+    let mut result = String::new();
+    for scope in scopes {
+        let status = if scope.is_cancelled() {
+            "cancelled".to_string()
+        } else if let Some(remaining) = scope.remaining() {
+            format!("active {}ms", remaining.as_millis())
+        } else {
+            "active".to_string()
+        };
+        result.push_str(&format!("{}\t{}\t{}\n", scope.id, scope.name, status));
+    }
+    result
+}
+
+fn find_scope(id: &str) -> Result<Arc<ControlScope>, String> {
+    let id = id
+        .parse::<u64>()
+        .map_err(|_| format!("invalid scope id `{id}`"))?;
+    let state = control_state();
+    let scope = state.scopes.iter().find(|it| it.id == id).cloned();
+    scope.ok_or_else(|| format!("no active scope with id {id}"))
+}
+
+/// Parse a whole number with an optional unit (`ms`, `s`, `m` or `h`; seconds by default).
+fn parse_duration(value: &str) -> Option<Duration> {
+    let split = value
+        .find(|it: char| !it.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<u64>().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(number)),
+        "" | "s" => Some(Duration::from_secs(number)),
+        "m" => Some(Duration::from_secs(number.checked_mul(60)?)),
+        "h" => Some(Duration::from_secs(number.checked_mul(3600)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::triggers::control::{MAX_CONNECTIONS, execute, parse_duration};
+    use crate::{CancelControl, CancellationTrigger, ControlServer};
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    #[test]
+    fn control_commands() {
+        let scope = CancelControl::new("first scope");
+        let timed = CancelControl::with_timeout("timed", Duration::from_millis(200));
+        let list = execute("list");
+        assert!(list.contains(&format!("{}\tfirst scope\tactive\n", scope.id())));
+        assert!(list.contains(&format!("{}\ttimed\tactive ", timed.id())));
+        assert!(list.ends_with("ok\n"));
+
+        assert_eq!(execute(&format!("extend {} 10s", scope.id())), {
+            format!("error: scope {} has no active deadline\n", scope.id())
+        });
+        assert_eq!(execute(&format!("extend {} 1m", timed.id())), "ok\n");
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(!timed.is_cancelled());
+
+        assert_eq!(execute(&format!("cancel {}", scope.id())), "ok\n");
+        assert!(scope.is_cancelled());
+        assert_eq!(
+            scope.detail(),
+            Some("canceled via control socket".to_string())
+        );
+        assert!(execute("list").contains(&format!("{}\tfirst scope\tcancelled\n", scope.id())));
+
+        // Dropped scopes are no longer listed.
+        let id = scope.id();
+        drop(scope);
+        assert_eq!(
+            execute(&format!("cancel {id}")),
+            format!("error: no active scope with id {id}\n")
+        );
+        assert_eq!(execute("stop"), "error: unknown command `stop`\n");
+        assert!(execute(&format!("extend {} 5x", timed.id())).starts_with("error: invalid"));
+    }
+
+    #[test]
+    fn control_deadline() {
+        let timed = CancelControl::with_timeout("deadline", Duration::from_millis(20));
+        assert!(!timed.is_cancelled());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(timed.is_cancelled());
+        assert_eq!(timed.detail(), Some("deadline elapsed".to_string()));
+        assert!(!timed.extend(Duration::from_secs(1)));
+
+        // Huge timeouts saturate instead of overflowing.
+        let forever = CancelControl::with_timeout("forever", Duration::MAX);
+        assert!(!forever.is_cancelled());
+        assert!(forever.extend(Duration::MAX));
+    }
+
+    #[test]
+    fn control_durations() {
+        assert_eq!(parse_duration("15"), Some(Duration::from_secs(15)));
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("ms"), None);
+        assert_eq!(parse_duration("1.5s"), None);
+    }
+
+    #[test]
+    fn control_socket() {
+        let path = std::env::temp_dir().join(format!("cancel-this-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = ControlServer::bind(&path).unwrap();
+        let scope = CancelControl::new("socket");
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+        writeln!(stream, "cancel {}", scope.id()).unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "ok");
+        assert!(scope.is_cancelled());
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn control_connection_limit() {
+        let path =
+            std::env::temp_dir().join(format!("cancel-this-limit-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = ControlServer::bind(&path).unwrap();
+
+        let connect = || {
+            let mut stream = UnixStream::connect(&path).unwrap();
+            let lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            // A rejected connection can be closed before the command is written.
+            let _ = writeln!(stream, "list");
+            let last = lines
+                .map(|it| it.unwrap())
+                .find(|it| it == "ok" || it.starts_with("error"));
+            (stream, last.unwrap())
+        };
+
+        let mut open = (0..MAX_CONNECTIONS).map(|_| connect()).collect::<Vec<_>>();
+        assert!(open.iter().all(|(_, last)| last == "ok"));
+        assert_eq!(connect().1, "error: too many connections");
+
+        // Once a connection is closed, a new one is served again.
+        open.pop();
+        let mut served = false;
+        for _ in 0..100 {
+            if connect().1 == "ok" {
+                served = true;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(served);
+
+        drop(open);
+        drop(server);
+    }
+
+    #[test]
+    fn control_socket_removed() {
+        let path =
+            std::env::temp_dir().join(format!("cancel-this-removed-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = ControlServer::bind(&path).unwrap();
+        // E.g., a cleanup of temporary files. The server still stops once dropped.
+        std::fs::remove_file(&path).unwrap();
+        drop(server);
+
+        // A replaced socket file belongs to another server and is kept.
+        let server = ControlServer::bind(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let other = ControlServer::bind(&path).unwrap();
+        drop(server);
+        assert!(path.exists());
+        drop(other);
+        assert!(!path.exists());
+    }
+}
@@ -1,7 +1,9 @@
-use crate::{CancelAtomic, CancellationTrigger, Cancelled};
+use crate::registration::Registration;
+use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerListener};
 use lazy_static::lazy_static;
-use log::trace;
-use std::sync::Mutex;
+use log::{trace, warn};
+use std::sync::{Arc, Mutex, MutexGuard, Once};
+use std::time::{Duration, Instant};
 
 /// Run the given `action`, cancelling it using [`CancelCtrlc`] if the `SIGINT` signal (Ctrl+C)
 /// is detected.
@@ -34,35 +36,146 @@ use std::sync::Mutex;
 /// assert!(result_slow.is_err());
 /// ```
 ///
-/// # Panics
-/// The operation can panic if the internal handler of the `ctrlc` crate has been already set
-/// by some other piece of code. Multiple registrations of the [`CancelCtrlc`] trigger are safely
+/// If the internal handler of the `ctrlc` crate has been already set by some other piece
+/// of code, the action is still executed, but it is only canceled once that handler calls
+/// [`notify_sigint`] (see [`CancelCtrlc::new`]). Use [`try_on_sigint`] if you want to detect
+/// this situation instead. Multiple registrations of the [`CancelCtrlc`] trigger are safely
 /// managed by this crate.
 pub fn on_sigint<TResult, TError, TAction>(action: TAction) -> Result<TResult, TError>
 where
     TAction: FnOnce() -> Result<TResult, TError>,
     TError: From<Cancelled>,
 {
-    crate::on_trigger(CancelCtrlc::default(), action)
+    crate::on_trigger(CancelCtrlc::new(), action)
 }
 
-/// Private global list of items waiting for ctrl+c to be pressed.
-static WAITING_FOR_CTRLC: Mutex<Vec<CancelAtomic>> = Mutex::new(Vec::new());
+/// The same as [`on_sigint`], but the `action` is not executed and an error is returned
+/// if the internal handler of the `ctrlc` crate cannot be set (see [`CancelCtrlc::try_new`]).
+pub fn try_on_sigint<TResult, TError, TAction>(
+    action: TAction,
+) -> Result<Result<TResult, TError>, &'static ctrlc::Error>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    let trigger = CancelCtrlc::try_new()?;
+    Ok(crate::on_trigger(trigger, action))
+}
+
+/// Cancel all [`CancelCtrlc`] triggers as if SIGINT was received by the global handler.
+///
+/// This is intended for applications that install their own Ctrl+C handler (making the
+/// global handler of this crate unavailable), but still want to cancel the operations running
+/// in [`on_sigint`] scopes. The call also respects the configuration of the global handler
+/// (see [`CancelCtrlc::set_force_exit_window`] and [`CancelCtrlc::set_interrupt_hook`]).
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, is_cancelled};
+/// # let _ = env_logger::builder().is_test(true).try_init();
+/// fn cancellable_counter(count: usize) -> Result<(), Cancelled> {
+///     for _ in 0..count {
+///         is_cancelled!()?;
+///         std::thread::sleep(Duration::from_millis(10));
+///     }
+///     Ok(())
+/// }
+///
+/// // The application installs its own handler, which also notifies `cancel_this`.
+/// ctrlc::set_handler(|| {
+///     eprintln!("Shutting down...");
+///     cancel_this::ctrlc::notify_sigint();
+/// }).unwrap();
+///
+/// std::thread::spawn(|| {
+///     // Wait for 100ms and then trigger SIGINT.
+///     std::thread::sleep(Duration::from_millis(100));
+///     let pid = std::process::id() as libc::pid_t; // Get current process ID
+///     unsafe { assert_eq!(libc::kill(pid, libc::SIGINT), 0) }
+/// });
+///
+/// // Since the `ctrlc` handler is already set, this scope fails to start...
+/// let result = cancel_this::try_on_sigint(|| cancellable_counter(3));
+/// assert!(result.is_err());
+///
+/// // ...but this one can still be canceled by the application handler.
+/// let result_slow = cancel_this::on_sigint(|| cancellable_counter(50));
+/// assert!(result_slow.is_err());
+/// ```
+pub fn notify_sigint() {
+    handle_sigint();
+}
+
+/// Private global state of the SIGINT handler shared by all [`CancelCtrlc`] triggers.
+static CTRLC_STATE: Mutex<CtrlcState> = Mutex::new(CtrlcState {
+    next_id: 0,
+    waiting: Vec::new(),
+    last_sigint: None,
+    force_exit_window: None,
+    interrupt_hook: None,
+});
+
+/// Exit code used when the process is terminated by a repeated SIGINT (`128 + SIGINT`).
+const FORCE_EXIT_CODE: i32 = 130;
+
+/// Optional callback invoked every time SIGINT is processed.
+type InterruptHook = Arc<dyn Fn() + Send + Sync>;
+
+struct CtrlcState {
+    /// Identifier assigned to the next registered trigger.
+    next_id: u64,
+    /// Triggers waiting for ctrl+c to be pressed, identified by their registration id.
+    waiting: Vec<(u64, CancelAtomic)>,
+    /// The time when the last SIGINT was processed.
+    last_sigint: Option<Instant>,
+    /// If set, a repeated SIGINT within this window terminates the process.
+    force_exit_window: Option<Duration>,
+    interrupt_hook: Option<InterruptHook>,
+}
+
+fn ctrlc_state() -> MutexGuard<'static, CtrlcState> {
+    CTRLC_STATE
+        .lock()
+        .expect("Global state of `CancelCtrlc` is corrupted.")
+}
+
+/// Process one SIGINT event: cancel all waiting triggers and run the interrupt hook, or
+/// terminate the process if this is a repeated SIGINT within the force-exit window.
+fn handle_sigint() {
+    let mut state = ctrlc_state();
+    let now = Instant::now();
+    if let (Some(window), Some(last)) = (state.force_exit_window, state.last_sigint)
+        && now.duration_since(last) <= window
+    {
+        drop(state);
+        warn!(
+            "Received repeated SIGINT within {}ms. Terminating process.",
+            window.as_millis()
+        );
+        std::process::exit(FORCE_EXIT_CODE);
+    }
+    state.last_sigint = Some(now);
+
+    // Go through all the pending triggers and cancel them. Triggers created after
+    // this point are waiting for the next SIGINT.
+    let total = state.waiting.len();
+    trace!("Received SIGINT. Cancelling triggers ({total} total).");
+    for (_, to_trigger) in state.waiting.drain(..) {
+        to_trigger.cancel();
+    }
+
+    // The hook is executed without holding the lock, since it can be arbitrary user code.
+    let hook = state.interrupt_hook.clone();
+    drop(state);
+    if let Some(hook) = hook {
+        hook();
+    }
+}
 
 lazy_static! {
     /// The result of ctrlc initialization, called exactly once before the first use of the
     /// ctrlc functionality.
-    static ref CTRLC_INITIALIZED: Result<(), ctrlc::Error> = ctrlc::try_set_handler(|| {
-        let mut guard = WAITING_FOR_CTRLC.lock()
-            .expect("Global state of `CancelCtrlc` is corrupted.");
-
-        // Go through all the pending triggers and cancel them.
-        let total = guard.len();
-        trace!("Received SIGINT. Cancelling triggers ({total} total).");
-        while let Some(to_trigger) = guard.pop() {
-            to_trigger.cancel();
-        }
-    });
+    static ref CTRLC_INITIALIZED: Result<(), ctrlc::Error> = ctrlc::try_set_handler(handle_sigint);
 }
 
 /// Implementation of [`CancellationTrigger`] that is canceled when SIGINT (Ctrl+C)
@@ -70,15 +183,31 @@ lazy_static! {
 ///
 /// This uses the `ctrlc` crate to observe the SIGINT events. As such, it
 /// needs to call `ctrlc::set_handler` upon first use, meaning it can fail if
-/// other features in your code also use `ctrlc`.
+/// other features in your code also use `ctrlc`. In such cases, the other handler
+/// can still cancel all triggers using [`notify_sigint`].
+///
+/// Each trigger waits for the first SIGINT that arrives after its creation. Once all copies
+/// of the trigger are dropped (typically at the end of the [`on_sigint`] scope), the trigger
+/// is unregistered from the global handler. Consequently, repeatedly entering [`on_sigint`]
+/// scopes (e.g., once per command of a long-running REPL) does not accumulate any global
+/// state, and every new scope is "re-armed", i.e., waiting for the next SIGINT.
+///
+/// Additionally, the global handler can be configured to terminate the process when
+/// SIGINT is received repeatedly (see [`CancelCtrlc::set_force_exit_window`]) and to notify
+/// the user about the cancellation (see [`CancelCtrlc::set_interrupt_hook`]).
 ///
 /// See also [`on_sigint`].
 ///
 /// ## Logging
 ///  - [`trace`] Every time the SIGINT event is processed, the number of affected triggers
 ///    is listed. Each trigger should also produce a message once actually canceled.
+///  - [`warn`] When the process is terminated due to a repeated SIGINT.
+///  - [`warn`] When the global handler is unavailable and [`notify_sigint`] has to be
+///    used instead (reported only once).
 #[derive(Debug, Clone)]
-pub struct CancelCtrlc(CancelAtomic);
+// The registration is only needed to unregister the trigger once all copies are dropped.
+#[allow(dead_code)]
+pub struct CancelCtrlc(CancelAtomic, Arc<Registration>);
 
 impl CancellationTrigger for CancelCtrlc {
     fn is_cancelled(&self) -> bool {
@@ -88,15 +217,41 @@ impl CancellationTrigger for CancelCtrlc {
     fn type_name(&self) -> &'static str {
         "CancelCtrlc"
     }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.0.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.unsubscribe(listener)
+    }
 }
 
 impl Default for CancelCtrlc {
     fn default() -> Self {
-        Self::try_new().unwrap()
+        Self::new()
     }
 }
 
 impl CancelCtrlc {
+    /// Create a new instance of [`CancelCtrlc`].
+    ///
+    /// If the initialization of the `ctrlc` handler wasn't successful (typically because the
+    /// application installed its own handler), the trigger is still created, but it is only
+    /// canceled once [`notify_sigint`] is called.
+    pub fn new() -> Self {
+        if let Err(e) = CTRLC_INITIALIZED.as_ref() {
+            static FALLBACK_WARNING: Once = Once::new();
+            FALLBACK_WARNING.call_once(|| {
+                warn!(
+                    "`CancelCtrlc` cannot set the SIGINT handler: {e:?}. \
+                     Use `cancel_this::ctrlc::notify_sigint` to cancel the triggers."
+                );
+            });
+        }
+        Self::register()
+    }
+
     /// Try to create a new instance of [`CancelCtrlc`], returning an error if
     /// the initialization of the `ctrlc` handler wasn't successful.
     ///
@@ -105,21 +260,62 @@ impl CancelCtrlc {
     pub fn try_new() -> Result<Self, &'static ctrlc::Error> {
         match CTRLC_INITIALIZED.as_ref() {
             Err(e) => Err(e),
-            Ok(_) => {
-                let trigger = CancelAtomic::default();
-                let mut guard = WAITING_FOR_CTRLC
-                    .lock()
-                    .expect("Global state of `CancelCtrlc` is corrupted.");
-                guard.push(trigger.clone());
-                Ok(CancelCtrlc(trigger))
-            }
+            Ok(_) => Ok(Self::register()),
         }
     }
+
+    /// Configure the global SIGINT handler to terminate the process (with exit code `130`)
+    /// when a second SIGINT arrives within the given `window` after the previous one.
+    /// Use `None` (default) to disable this behavior.
+    ///
+    /// This is useful for applications where the cancellation can take a long time,
+    /// so that the user still has a way to stop the process immediately.
+    pub fn set_force_exit_window(window: Option<Duration>) {
+        ctrlc_state().force_exit_window = window;
+    }
+
+    /// Configure a `hook` that is executed every time the global SIGINT handler processes
+    /// a SIGINT event (after all waiting triggers are canceled). The hook is executed
+    /// on the handler thread of the `ctrlc` crate.
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use cancel_this::CancelCtrlc;
+    /// CancelCtrlc::set_force_exit_window(Some(Duration::from_secs(2)));
+    /// CancelCtrlc::set_interrupt_hook(|| {
+    ///     eprintln!("Cancelling... press Ctrl+C again to force quit.");
+    /// });
+    /// ```
+    pub fn set_interrupt_hook<F: Fn() + Send + Sync + 'static>(hook: F) {
+        ctrlc_state().interrupt_hook = Some(Arc::new(hook));
+    }
+
+    /// Remove the hook previously configured using [`CancelCtrlc::set_interrupt_hook`].
+    pub fn clear_interrupt_hook() {
+        ctrlc_state().interrupt_hook = None;
+    }
+
+    /// Register a new trigger with the global SIGINT handler.
+    fn register() -> Self {
+        let trigger = CancelAtomic::default();
+        let mut state = ctrlc_state();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiting.push((id, trigger.clone()));
+        let registration = Registration::new(&CTRLC_STATE, move |mut state| {
+            // The trigger is not waiting if it has already been canceled.
+            state.waiting.retain(|(it, _)| *it != id);
+        });
+        CancelCtrlc(trigger, Arc::new(registration))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::CancelCtrlc;
+    use crate::triggers::ctrlc::{ctrlc_state, handle_sigint};
+    use crate::{CancelCtrlc, CancellationTrigger};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[test]
     fn ctrlc_twice() {
@@ -130,5 +326,96 @@ mod tests {
 
         let trigger = CancelCtrlc::try_new();
         assert!(trigger.is_err());
+
+        // The fallback trigger is registered even without the global handler.
+        let fallback = CancelCtrlc::new();
+        assert!(!fallback.is_cancelled());
+        let state = ctrlc_state();
+        assert!(state.waiting.iter().any(|(_, it)| it.same_as(&fallback.0)));
+    }
+
+    #[test]
+    fn ctrlc_registration() {
+        let is_waiting = |trigger: &CancelCtrlc| {
+            let state = ctrlc_state();
+            state.waiting.iter().any(|(_, it)| it.same_as(&trigger.0))
+        };
+
+        let hook_calls = Arc::new(AtomicUsize::new(0));
+        let hook_calls_copy = hook_calls.clone();
+        CancelCtrlc::set_interrupt_hook(move || {
+            hook_calls_copy.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Dropping all copies of a trigger unregisters it.
+        let dropped = CancelCtrlc::register();
+        let dropped_copy = dropped.clone();
+        assert!(is_waiting(&dropped));
+        drop(dropped);
+        assert!(is_waiting(&dropped_copy));
+        let dropped_flag = dropped_copy.0.clone();
+        drop(dropped_copy);
+        assert!(
+            !ctrlc_state()
+                .waiting
+                .iter()
+                .any(|(_, it)| it.same_as(&dropped_flag))
+        );
+
+        // SIGINT cancels the waiting triggers and runs the hook.
+        let first = CancelCtrlc::register();
+        handle_sigint();
+        assert!(first.is_cancelled());
+        assert!(!is_waiting(&first));
+        assert_eq!(hook_calls.load(Ordering::SeqCst), 1);
+
+        // New triggers are waiting for the next SIGINT.
+        let second = CancelCtrlc::register();
+        assert!(!second.is_cancelled());
+        handle_sigint();
+        assert!(second.is_cancelled());
+        assert_eq!(hook_calls.load(Ordering::SeqCst), 2);
+
+        CancelCtrlc::clear_interrupt_hook();
+    }
+
+    /// Set if [`ctrlc_force_exit_child`] runs as a child process of [`ctrlc_force_exit`].
+    #[cfg(unix)]
+    const FORCE_EXIT_CHILD: &str = "CANCEL_THIS_TEST_FORCE_EXIT_CHILD";
+
+    #[cfg(unix)]
+    #[test]
+    fn ctrlc_force_exit() {
+        // The child runs in a fresh copy of the test binary, such that the SIGINT handler
+        // is not shared with other tests.
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "triggers::ctrlc::tests::ctrlc_force_exit_child",
+                "--ignored",
+                "--test-threads=1",
+            ])
+            .env(FORCE_EXIT_CHILD, "1")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(super::FORCE_EXIT_CODE));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "only runs as a child process of `ctrlc_force_exit`"]
+    fn ctrlc_force_exit_child() {
+        assert!(
+            std::env::var_os(FORCE_EXIT_CHILD).is_some(),
+            "This test only runs as a part of `ctrlc_force_exit`."
+        );
+        CancelCtrlc::set_force_exit_window(Some(std::time::Duration::from_secs(10)));
+        let trigger = CancelCtrlc::try_new().unwrap();
+        unsafe { assert_eq!(libc::raise(libc::SIGINT), 0) };
+        crate::triggers::wait_for(&trigger);
+        unsafe { assert_eq!(libc::raise(libc::SIGINT), 0) };
+        // The process is terminated by the second SIGINT.
+        std::thread::sleep(std::time::Duration::from_secs(10));
+        unreachable!("The process was not terminated by a repeated SIGINT.");
     }
 }
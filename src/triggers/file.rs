@@ -0,0 +1,355 @@
+use crate::triggers::watcher::{Watch, WatchTrigger, register_watch};
+use crate::{CancellationTrigger, Cancelled, TriggerListener};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Run the given `action`, cancelling it using [`CancelFile::when_exists`] once a file
+/// exists at the given `path`.
+///
+/// This allows operators to gracefully stop a long-running job by simply creating
+/// a "sentinel" file (e.g., `touch ./STOP`), without sending any signals.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, is_cancelled};
+/// # let _ = env_logger::builder().is_test(true).try_init();
+/// fn cancellable_counter(count: usize) -> Result<(), Cancelled> {
+///     for _ in 0..count {
+///         is_cancelled!()?;
+///         std::thread::sleep(Duration::from_millis(10));
+///     }
+///     Ok(())
+/// }
+///
+/// let stop = std::env::temp_dir().join(format!("cancel-this-doc-{}.stop", std::process::id()));
+/// let stop_copy = stop.clone();
+/// std::thread::spawn(move || {
+///     // Wait for 100ms and then create the sentinel file.
+///     std::thread::sleep(Duration::from_millis(100));
+///     std::fs::write(stop_copy, "").unwrap();
+/// });
+///
+/// // The first action is fast (30ms) and should complete before the file is created.
+/// let result_fast = cancel_this::on_file(&stop, || cancellable_counter(3));
+/// assert!(result_fast.is_ok());
+///
+/// // The second action is slow and will be canceled once the file is created.
+/// let result_slow = cancel_this::on_file(&stop, || cancellable_counter(500));
+/// assert_eq!(result_slow.unwrap_err().cause(), "CancelFile");
+/// # std::fs::remove_file(stop).unwrap();
+/// ```
+///
+/// # Panics
+/// The operation panics if the file cannot be observed (see [`CancelFile::try_when_exists`]).
+pub fn on_file<TResult, TError, TAction, TPath>(
+    path: TPath,
+    action: TAction,
+) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+    TPath: AsRef<Path>,
+{
+    crate::on_trigger(CancelFile::when_exists(path), action)
+}
+
+/// The polling interval used when the file cannot be observed using `inotify`.
+const FILE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Implementation of [`CancellationTrigger`] that is canceled based on the state of a file,
+/// either once the file exists ([`CancelFile::when_exists`]), or once it is modified
+/// ([`CancelFile::when_modified`]). The path of the file is reported through
+/// [`Cancelled::detail`].
+///
+/// On Linux, the parent directory of the file is observed using `inotify`, meaning the
+/// changes are detected almost immediately. On other platforms (or if the parent directory
+/// does not exist), the file is polled periodically (every `100ms`). In both cases, the actual
+/// observation is performed by a shared watcher thread, meaning cancellation checks are as
+/// cheap as [`crate::CancelAtomic`].
+///
+/// See also [`on_file`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled.
+#[derive(Debug, Clone)]
+pub struct CancelFile {
+    path: PathBuf,
+    watch: WatchTrigger,
+}
+
+impl CancellationTrigger for CancelFile {
+    fn is_cancelled(&self) -> bool {
+        self.watch.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelFile"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.watch.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.watch.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.watch.detail()
+    }
+}
+
+impl CancelFile {
+    /// Create a new [`CancelFile`] that is canceled once a file (or directory) exists
+    /// at the given `path`. If the file already exists, the trigger is canceled immediately.
+    ///
+    /// # Panics
+    /// The operation panics if the file cannot be observed
+    /// (see [`CancelFile::try_when_exists`]).
+    pub fn when_exists<T: AsRef<Path>>(path: T) -> Self {
+        Self::try_when_exists(path).unwrap()
+    }
+
+    /// Create a new [`CancelFile`] that is canceled once the file at the given `path` is
+    /// modified, i.e., its modification time or size changes compared to the moment when
+    /// the trigger was created. Creating or removing the file is also considered
+    /// a modification.
+    ///
+    /// # Panics
+    /// The operation panics if the file cannot be observed
+    /// (see [`CancelFile::try_when_modified`]).
+    pub fn when_modified<T: AsRef<Path>>(path: T) -> Self {
+        Self::try_when_modified(path).unwrap()
+    }
+
+    /// The same as [`CancelFile::when_exists`], but returns an error if the shared watcher
+    /// thread cannot be started.
+    pub fn try_when_exists<T: AsRef<Path>>(path: T) -> Result<Self, std::io::Error> {
+        Self::watch(
+            path.as_ref(),
+            FileCondition::Exists,
+            cfg!(target_os = "linux"),
+        )
+    }
+
+    /// The same as [`CancelFile::when_modified`], but returns an error if the shared watcher
+    /// thread cannot be started.
+    pub fn try_when_modified<T: AsRef<Path>>(path: T) -> Result<Self, std::io::Error> {
+        let original = FileStamp::read(path.as_ref());
+        let condition = FileCondition::Modified(original);
+        Self::watch(path.as_ref(), condition, cfg!(target_os = "linux"))
+    }
+
+    /// The path of the observed file.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Create the trigger, using `inotify` only if `use_inotify` is set (otherwise, polling
+    /// is used).
+    fn watch(
+        path: &Path,
+        condition: FileCondition,
+        use_inotify: bool,
+    ) -> Result<Self, std::io::Error> {
+        let path = path.to_path_buf();
+        let detail = match condition {
+            FileCondition::Exists => format!("file {} exists", path.display()),
+            FileCondition::Modified(_) => format!("file {} modified", path.display()),
+        };
+        let probe_path = path.clone();
+        let is_met = move || condition.is_met(&probe_path);
+
+        // The directory has to be observed before the initial check, otherwise we could
+        // miss a change that happens in between.
+        #[cfg(target_os = "linux")]
+        let inotify = if use_inotify {
+            open_inotify(&path)
+        } else {
+            None
+        };
+        #[cfg(not(target_os = "linux"))]
+        let _ = use_inotify;
+
+        let initially_met = is_met();
+        let probe_detail = detail.clone();
+
+        #[cfg(target_os = "linux")]
+        let watch = match inotify {
+            Some(inotify) => {
+                use std::os::fd::AsRawFd;
+                Watch {
+                    fd: Some((inotify.as_raw_fd(), libc::POLLIN)),
+                    interval: None,
+                    probe: Box::new(move |_| {
+                        drain_inotify(&inotify);
+                        is_met().then(|| probe_detail.clone())
+                    }),
+                }
+            }
+            None => polling_watch(is_met, probe_detail),
+        };
+        #[cfg(not(target_os = "linux"))]
+        let watch = polling_watch(is_met, probe_detail);
+
+        let watch = register_watch("CancelFile", watch)?;
+        if initially_met {
+            watch.cancel(detail);
+        }
+        Ok(CancelFile { path, watch })
+    }
+}
+
+/// A [`Watch`] which periodically checks the given condition.
+fn polling_watch<F>(is_met: F, detail: String) -> Watch
+where
+    F: Fn() -> bool + Send + 'static,
+{
+    Watch {
+        fd: None,
+        interval: Some(FILE_POLL_INTERVAL),
+        probe: Box::new(move |_| is_met().then(|| detail.clone())),
+    }
+}
+
+/// The condition that cancels a [`CancelFile`] trigger.
+#[derive(Debug, Clone, Copy)]
+enum FileCondition {
+    Exists,
+    /// Canceled once the stamp differs from the original one (`None` if the file did not
+    /// exist initially).
+    Modified(Option<FileStamp>),
+}
+
+impl FileCondition {
+    fn is_met(&self, path: &Path) -> bool {
+        match self {
+            FileCondition::Exists => path.exists(),
+            FileCondition::Modified(original) => FileStamp::read(path) != *original,
+        }
+    }
+}
+
+/// A lightweight summary of the file metadata used to detect modifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> Option<FileStamp> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(FileStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Create an `inotify` instance observing the parent directory of the given `path`, or return
+/// `None` if this is not possible (e.g., the directory does not exist).
+#[cfg(target_os = "linux")]
+fn open_inotify(path: &Path) -> Option<std::os::fd::OwnedFd> {
+    use std::ffi::CString;
+    use std::os::fd::FromRawFd;
+    use std::os::unix::ffi::OsStrExt;
+
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let directory = CString::new(directory.as_os_str().as_bytes()).ok()?;
+    let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return None;
+    }
+    let inotify = unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) };
+    let mask = libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MODIFY
+        | libc::IN_ATTRIB
+        | libc::IN_CLOSE_WRITE
+        | libc::IN_MOVED_TO
+        | libc::IN_MOVED_FROM;
+    let watch = unsafe { libc::inotify_add_watch(fd, directory.as_ptr(), mask) };
+    if watch < 0 { None } else { Some(inotify) }
+}
+
+/// Read all pending `inotify` events. The events themselves are not important,
+/// since the condition is always checked using the file metadata.
+#[cfg(target_os = "linux")]
+fn drain_inotify(inotify: &std::os::fd::OwnedFd) {
+    use std::os::fd::AsRawFd;
+    let mut buffer = [0u8; 4096];
+    loop {
+        let read = unsafe {
+            libc::read(
+                inotify.as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        if read <= 0 {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::triggers::file::{FileCondition, FileStamp};
+    use crate::triggers::wait_for;
+    use crate::{CancelFile, CancellationTrigger};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// A path in the temporary directory that is unique for this process and test.
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cancel-this-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn file_exists() {
+        for use_inotify in [true, false] {
+            let path = temp_path(&format!("exists-{use_inotify}"));
+            let trigger = CancelFile::watch(&path, FileCondition::Exists, use_inotify).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(!trigger.is_cancelled());
+
+            std::fs::write(&path, "stop").unwrap();
+            wait_for(&trigger);
+            assert_eq!(
+                trigger.detail(),
+                Some(format!("file {} exists", path.display()))
+            );
+
+            // Existing file cancels the trigger immediately.
+            assert!(CancelFile::when_exists(&path).is_cancelled());
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn file_modified() {
+        for use_inotify in [true, false] {
+            let path = temp_path(&format!("modified-{use_inotify}"));
+            std::fs::write(&path, "original").unwrap();
+            let original = FileStamp::read(&path);
+            let condition = FileCondition::Modified(original);
+            let trigger = CancelFile::watch(&path, condition, use_inotify).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(!trigger.is_cancelled());
+
+            std::fs::write(&path, "modified content").unwrap();
+            wait_for(&trigger);
+            assert_eq!(
+                trigger.detail(),
+                Some(format!("file {} modified", path.display()))
+            );
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
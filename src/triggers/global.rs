@@ -0,0 +1,339 @@
+use crate::{CancellationTrigger, Cancelled, TriggerListener, check_cancellation};
+use lazy_static::lazy_static;
+use log::trace;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// The [`Cancelled`] cause reported once [`shutdown`] is called.
+pub const SHUTDOWN_CAUSE: &str = "Shutdown";
+
+/// Install a process-global `trigger` which is consulted by [`crate::is_cancelled`] in every
+/// thread (in addition to the thread-local triggers), including threads that never registered
+/// any triggers themselves (e.g., worker threads of third-party libraries). Returns an id which
+/// can be used to remove the trigger using [`uninstall_global`].
+///
+/// Global triggers also apply to triggers obtained using [`crate::active_triggers`] and within
+/// [`crate::never`] scopes. While no global trigger is installed, this feature adds
+/// no overhead to cancellation checks.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, CancelAtomic, is_cancelled};
+/// let trigger = CancelAtomic::new();
+/// let id = cancel_this::install_global(trigger.clone());
+///
+/// // A thread which knows nothing about the trigger.
+/// let worker = std::thread::spawn(|| -> Result<(), Cancelled> {
+///     loop {
+///         is_cancelled!()?;
+///         std::thread::sleep(Duration::from_millis(10));
+///     }
+/// });
+///
+/// trigger.cancel();
+/// assert_eq!(worker.join().unwrap().unwrap_err().cause(), "CancelAtomic");
+/// assert!(cancel_this::uninstall_global(id));
+/// ```
+pub fn install_global<TCancel: CancellationTrigger + 'static>(trigger: TCancel) -> u64 {
+    let mut state = global_state_mut();
+    let id = state.next_id;
+    state.next_id += 1;
+    trace!(
+        "Installing global `{}` trigger ({id}).",
+        trigger.type_name()
+    );
+    Arc::make_mut(&mut state.triggers).push((id, Arc::new(trigger)));
+    GLOBAL_COUNT.store(state.triggers.len(), Ordering::Release);
+    for listener in global_listeners().iter() {
+        listener.notify();
+    }
+    id
+}
+
+/// Remove the global trigger previously installed by [`install_global`]. Returns `false`
+/// if no such trigger is installed.
+pub fn uninstall_global(id: u64) -> bool {
+    let mut state = global_state_mut();
+    let count = state.triggers.len();
+    Arc::make_mut(&mut state.triggers).retain(|(it, _)| *it != id);
+    GLOBAL_COUNT.store(state.triggers.len(), Ordering::Release);
+    count != state.triggers.len()
+}
+
+/// Cancel all cooperative work in the process, i.e., every subsequent [`crate::is_cancelled`]
+/// check in any thread fails with the [`SHUTDOWN_CAUSE`]. This cannot be undone.
+///
+/// This is implemented as a global trigger (see [`install_global`]).
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, SHUTDOWN_CAUSE, is_cancelled};
+/// let workers = (0..4)
+///     .map(|_| {
+///         std::thread::spawn(|| -> Result<(), Cancelled> {
+///             loop {
+///                 is_cancelled!()?;
+///                 std::thread::sleep(Duration::from_millis(10));
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+///
+/// cancel_this::shutdown();
+/// for worker in workers {
+///     assert_eq!(worker.join().unwrap().unwrap_err().cause(), SHUTDOWN_CAUSE);
+/// }
+/// ```
+pub fn shutdown() {
+    if !SHUTDOWN.swap(true, Ordering::SeqCst) {
+        install_global(CancelShutdown);
+    }
+}
+
+/// Set once [`shutdown`] is called, to avoid installing the trigger repeatedly.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// The global trigger installed by [`shutdown`].
+#[derive(Debug, Clone, Copy)]
+struct CancelShutdown;
+
+impl CancellationTrigger for CancelShutdown {
+    fn is_cancelled(&self) -> bool {
+        true
+    }
+
+    fn type_name(&self) -> &'static str {
+        SHUTDOWN_CAUSE
+    }
+}
+
+/// Run the `check` of local triggers and, unless an enclosing check already does so,
+/// also check the installed global triggers. This way, global triggers are evaluated
+/// once per cancellation check, even if the local triggers contain snapshots (see
+/// [`crate::TriggerSnapshot`]), which check global triggers themselves.
+pub(crate) fn check_with_globals(check: impl FnOnce() -> Option<Cancelled>) -> Option<Cancelled> {
+    if GLOBAL_COUNT.load(Ordering::Acquire) == 0 || CHECKING_GLOBALS.get() {
+        return check();
+    }
+    CHECKING_GLOBALS.set(true);
+    let _checking = GlobalCheckGuard;
+    check().or_else(|| check_global_cancellation().err())
+}
+
+thread_local! {
+    /// True while [`check_with_globals`] is running in this thread.
+    static CHECKING_GLOBALS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Resets [`CHECKING_GLOBALS`] once dropped.
+struct GlobalCheckGuard;
+
+impl Drop for GlobalCheckGuard {
+    fn drop(&mut self) {
+        CHECKING_GLOBALS.set(false);
+    }
+}
+
+/// True if at least one global trigger is installed.
+pub(crate) fn has_globals() -> bool {
+    GLOBAL_COUNT.load(Ordering::Acquire) > 0
+}
+
+/// Register the `listener` to be notified once a global trigger is installed. If a global
+/// trigger is already installed, the listener is notified immediately.
+pub(crate) fn subscribe_globals(listener: &TriggerListener) {
+    let mut listeners = global_listeners();
+    listeners.push(listener.clone());
+    if has_globals() {
+        listener.notify();
+    }
+}
+
+/// Remove the `listener` previously registered by [`subscribe_globals`].
+pub(crate) fn unsubscribe_globals(listener: &TriggerListener) {
+    let mut listeners = global_listeners();
+    if let Some(index) = listeners.iter().position(|it| it.same_as(listener)) {
+        listeners.swap_remove(index);
+    }
+}
+
+/// Returns [`Cancelled`] if any of the installed global triggers is canceled.
+fn check_global_cancellation() -> Result<(), Cancelled> {
+    if GLOBAL_COUNT.load(Ordering::Acquire) == 0 {
+        return Ok(());
+    }
+    installed_triggers()
+        .iter()
+        .try_for_each(|(_, trigger)| check_cancellation(trigger.as_ref()))
+}
+
+/// Find the first canceled global trigger (if any).
+pub(crate) fn cancelled_global() -> Option<Arc<dyn CancellationTrigger>> {
+    if GLOBAL_COUNT.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+    installed_triggers()
+        .iter()
+        .find(|(_, trigger)| trigger.is_cancelled())
+        .map(|(_, trigger)| trigger.clone())
+}
+
+/// The installed global triggers. The list is copied out of the lock, such that the triggers
+/// can install or uninstall global triggers themselves.
+fn installed_triggers() -> GlobalTriggers {
+    global_state().triggers.clone()
+}
+
+/// The list is replaced on every update (copy-on-write), so it can be checked without
+/// holding the lock.
+type GlobalTriggers = Arc<Vec<(u64, Arc<dyn CancellationTrigger>)>>;
+
+struct GlobalState {
+    next_id: u64,
+    triggers: GlobalTriggers,
+}
+
+lazy_static! {
+    /// Private global list of installed triggers. The number of triggers is mirrored in
+    /// [`GLOBAL_COUNT`], so that the list does not need to be locked when it is empty.
+    static ref GLOBAL_STATE: RwLock<GlobalState> = RwLock::new(GlobalState {
+        next_id: 0,
+        triggers: Arc::new(Vec::new()),
+    });
+}
+
+static GLOBAL_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Private global list of listeners notified once a global trigger is installed
+/// (see [`subscribe_globals`]).
+static GLOBAL_LISTENERS: Mutex<Vec<TriggerListener>> = Mutex::new(Vec::new());
+
+fn global_listeners() -> MutexGuard<'static, Vec<TriggerListener>> {
+    GLOBAL_LISTENERS
+        .lock()
+        .expect("Global state of global trigger listeners is corrupted.")
+}
+
+fn global_state() -> RwLockReadGuard<'static, GlobalState> {
+    GLOBAL_STATE
+        .read()
+        .expect("Global state of global triggers is corrupted.")
+}
+
+fn global_state_mut() -> RwLockWriteGuard<'static, GlobalState> {
+    GLOBAL_STATE
+        .write()
+        .expect("Global state of global triggers is corrupted.")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancellationTrigger, install_global, is_cancelled, uninstall_global};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread::ThreadId;
+
+    /// A trigger which is only canceled in the given thread, so that installing it globally
+    /// does not affect other tests.
+    #[derive(Clone)]
+    struct CancelThread(ThreadId);
+
+    impl CancellationTrigger for CancelThread {
+        fn is_cancelled(&self) -> bool {
+            std::thread::current().id() == self.0
+        }
+
+        fn type_name(&self) -> &'static str {
+            "CancelThread"
+        }
+    }
+
+    #[test]
+    fn global_triggers() {
+        let worker = std::thread::spawn(|| {
+            // Wait until the trigger is installed.
+            while is_cancelled!().is_ok() {
+                std::thread::yield_now();
+            }
+            let error = is_cancelled!().unwrap_err();
+            assert_eq!(error.cause(), "CancelThread");
+
+            // Global triggers also apply to the copies of thread-local triggers.
+            let active = crate::active_triggers();
+            assert_eq!(active.type_name(), "CancelThread");
+            assert!(is_cancelled!(active).is_err());
+        });
+
+        let id = install_global(CancelThread(worker.thread().id()));
+        assert!(is_cancelled!().is_ok());
+        worker.join().unwrap();
+        assert!(uninstall_global(id));
+        assert!(!uninstall_global(id));
+    }
+
+    /// A trigger which counts how many times it was checked in the given thread.
+    #[derive(Clone)]
+    struct CountThread(ThreadId, Arc<AtomicU64>);
+
+    impl CancellationTrigger for CountThread {
+        fn is_cancelled(&self) -> bool {
+            if std::thread::current().id() == self.0 {
+                self.1.fetch_add(1, Ordering::SeqCst);
+            }
+            false
+        }
+
+        fn type_name(&self) -> &'static str {
+            "CountThread"
+        }
+    }
+
+    #[test]
+    fn global_triggers_checked_once() {
+        let count = Arc::new(AtomicU64::new(0));
+        let id = install_global(CountThread(std::thread::current().id(), count.clone()));
+        let snapshot = crate::active_triggers();
+        assert!(is_cancelled!(snapshot).is_ok());
+        assert_eq!(count.swap(0, Ordering::SeqCst), 1);
+
+        // Snapshots used as thread-local triggers do not check global triggers again.
+        let result = crate::on_trigger(snapshot.clone(), || {
+            crate::on_trigger(snapshot, || {
+                count.store(0, Ordering::SeqCst);
+                is_cancelled!()?;
+                assert_eq!(count.load(Ordering::SeqCst), 1);
+                Ok::<_, crate::Cancelled>(())
+            })
+        });
+        assert!(result.is_ok());
+        assert!(uninstall_global(id));
+    }
+
+    /// A one-shot trigger which uninstalls itself once checked in the given thread.
+    #[derive(Clone)]
+    struct CancelOnce(ThreadId, Arc<AtomicU64>);
+
+    impl CancellationTrigger for CancelOnce {
+        fn is_cancelled(&self) -> bool {
+            if std::thread::current().id() != self.0 {
+                return false;
+            }
+            uninstall_global(self.1.load(Ordering::SeqCst))
+        }
+
+        fn type_name(&self) -> &'static str {
+            "CancelOnce"
+        }
+    }
+
+    #[test]
+    fn global_trigger_uninstalls_itself() {
+        let id = Arc::new(AtomicU64::new(u64::MAX));
+        let trigger = CancelOnce(std::thread::current().id(), id.clone());
+        id.store(install_global(trigger), Ordering::SeqCst);
+        assert_eq!(is_cancelled!().unwrap_err().cause(), "CancelOnce");
+        assert!(is_cancelled!().is_ok());
+        assert!(!uninstall_global(id.load(Ordering::SeqCst)));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::registration::Registration;
+use crate::{
+    CancelAtomic, CancellationTrigger, Cancelled, TRIGGER, TriggerListener, refresh_thread_triggers,
+};
+use log::trace;
+use std::cell::Cell;
+use std::sync::{Mutex, MutexGuard};
+use std::thread::ThreadId;
+
+/// The [`crate::Cancelled`] cause reported when a thread is interrupted using [`interrupt`].
+pub const INTERRUPTED_CAUSE: &str = "Interrupted";
+
+/// Interrupt the thread with the given id, meaning every subsequent [`crate::is_cancelled`]
+/// check in that thread fails with the [`INTERRUPTED_CAUSE`], until the thread calls
+/// [`clear_interrupt`]. Triggers obtained in that thread using [`crate::active_triggers`]
+/// (e.g., to initialize cancellation in other threads) are interrupted as well.
+///
+/// Returns `false` if the thread is not known, i.e., it has already terminated, or it has not
+/// used any cancellation features yet (in which case it cannot be interrupted).
+///
+/// Interrupts of unknown threads are not remembered, since a thread that has not started
+/// checking cancellation cannot be told apart from a thread that has already terminated.
+/// Consequently, interrupting a freshly spawned thread can fail, and the interrupt should be
+/// repeated until the thread is known (as in the example below), or the thread should notify
+/// the caller once it started checking cancellation.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, INTERRUPTED_CAUSE, is_cancelled};
+/// let worker = std::thread::spawn(|| -> Result<(), Cancelled> {
+///     loop {
+///         is_cancelled!()?;
+///         std::thread::sleep(Duration::from_millis(10));
+///     }
+/// });
+///
+/// // Wait until the worker starts checking cancellation, then interrupt it.
+/// while !cancel_this::interrupt(worker.thread().id()) {
+///     std::thread::sleep(Duration::from_millis(1));
+/// }
+/// let error = worker.join().unwrap().unwrap_err();
+/// assert_eq!(error.cause(), INTERRUPTED_CAUSE);
+/// ```
+pub fn interrupt(thread: ThreadId) -> bool {
+    let state = interrupt_state();
+    match state.iter().find(|(id, _)| *id == thread) {
+        None => false,
+        Some((_, flag)) => {
+            trace!("Interrupting thread {thread:?}.");
+            flag.0.cancel();
+            true
+        }
+    }
+}
+
+/// Reset the interrupt flag of the current thread, returning `true` if the thread
+/// was interrupted.
+pub fn clear_interrupt() -> bool {
+    let was_interrupted = CancelInterrupt::current().0.reset();
+    if was_interrupted {
+        // The thread-local triggers could still consider the interrupt as "fired".
+        let _ = TRIGGER.try_with(|_| refresh_thread_triggers());
+    }
+    was_interrupted
+}
+
+/// Implementation of [`CancellationTrigger`] which is implicitly checked by every thread
+/// (after the thread-local trigger chain) and is canceled using [`interrupt`]. The flag is
+/// not a part of the chain, but it is copied into the snapshots of the thread-local triggers
+/// (see [`crate::active_triggers`]).
+///
+/// Unlike other triggers, the cancellation can be reset using [`clear_interrupt`].
+#[derive(Debug, Clone)]
+pub(crate) struct CancelInterrupt(CancelAtomic);
+
+impl CancellationTrigger for CancelInterrupt {
+    fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        INTERRUPTED_CAUSE
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.0.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.unsubscribe(listener)
+    }
+}
+
+impl CancelInterrupt {
+    /// The interrupt flag of the current thread.
+    pub(crate) fn current() -> Self {
+        THREAD_INTERRUPT
+            .try_with(|it| it.0.clone())
+            // The thread is being destroyed, so it cannot be interrupted anymore.
+            .unwrap_or_else(|_| CancelInterrupt(CancelAtomic::default()))
+    }
+
+    /// The interrupt flag of the current thread, unless interrupts are suspended
+    /// (see [`suspend_interrupt`]).
+    pub(crate) fn active() -> Option<Self> {
+        if INTERRUPT_SUSPENDED.get() {
+            None
+        } else {
+            Some(Self::current())
+        }
+    }
+}
+
+/// Returns [`Cancelled`] if the current thread is interrupted (and interrupts
+/// are not suspended).
+pub(crate) fn check_interrupt() -> Option<Cancelled> {
+    if INTERRUPT_SUSPENDED.get() {
+        return None;
+    }
+    THREAD_INTERRUPT
+        .try_with(|it| it.0.check())
+        .unwrap_or_default()
+}
+
+/// Ignore the interrupt flag of the current thread until the returned guard is dropped
+/// (see [`crate::never`]).
+pub(crate) fn suspend_interrupt() -> SuspendedInterrupt {
+    SuspendedInterrupt(INTERRUPT_SUSPENDED.replace(true))
+}
+
+/// Restores the previous state of interrupts once dropped (see [`suspend_interrupt`]).
+pub(crate) struct SuspendedInterrupt(bool);
+
+impl Drop for SuspendedInterrupt {
+    fn drop(&mut self) {
+        INTERRUPT_SUSPENDED.set(self.0);
+    }
+}
+
+thread_local! {
+    /// The interrupt flag of this thread, registered in [`INTERRUPT_STATE`] while
+    /// the thread is running.
+    static THREAD_INTERRUPT: InterruptRegistration = InterruptRegistration::register();
+
+    /// True within [`crate::never`] scopes, where the interrupt flag does not apply.
+    static INTERRUPT_SUSPENDED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Private global list of interrupt flags of all threads that use cancellation.
+static INTERRUPT_STATE: Mutex<Vec<(ThreadId, CancelInterrupt)>> = Mutex::new(Vec::new());
+
+fn interrupt_state() -> MutexGuard<'static, Vec<(ThreadId, CancelInterrupt)>> {
+    INTERRUPT_STATE
+        .lock()
+        .expect("Global state of thread interrupts is corrupted.")
+}
+
+/// The interrupt flag of a thread, unregistered once the thread terminates.
+struct InterruptRegistration(CancelInterrupt, #[allow(dead_code)] Registration);
+
+impl InterruptRegistration {
+    fn register() -> Self {
+        let flag = CancelInterrupt(CancelAtomic::default());
+        let thread = std::thread::current().id();
+        interrupt_state().push((thread, flag.clone()));
+        let registered = flag.clone();
+        let registration = Registration::new(&INTERRUPT_STATE, move |mut state| {
+            state.retain(|(_, flag)| !flag.0.same_as(&registered.0));
+        });
+        InterruptRegistration(flag, registration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CancelAtomic, Cancellable, INTERRUPTED_CAUSE, clear_interrupt, interrupt, is_cancelled,
+    };
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn interrupt_thread() {
+        let (interrupted, wait_interrupted) = channel();
+        let (resume, wait_resume) = channel::<()>();
+        let worker = std::thread::spawn(move || {
+            let trigger = CancelAtomic::new();
+            crate::on_atomic(trigger, || -> Cancellable<()> {
+                is_cancelled!()?;
+                interrupted.send(()).unwrap();
+                wait_resume.recv().unwrap();
+                let error = is_cancelled!().unwrap_err();
+                assert_eq!(error.cause(), INTERRUPTED_CAUSE);
+                // Triggers transferred to other threads are interrupted too.
+                let active = crate::active_triggers();
+                assert!(is_cancelled!(active).is_err());
+
+                assert!(clear_interrupt());
+                assert!(!clear_interrupt());
+                is_cancelled!()
+            })
+        });
+
+        wait_interrupted.recv().unwrap();
+        assert!(interrupt(worker.thread().id()));
+        resume.send(()).unwrap();
+        assert!(worker.join().unwrap().is_ok());
+
+        // Once terminated, the thread can no longer be interrupted.
+        let finished = std::thread::spawn(|| {
+            let _ = is_cancelled!();
+        });
+        let finished_id = finished.thread().id();
+        finished.join().unwrap();
+        assert!(!interrupt(finished_id));
+    }
+
+    #[test]
+    fn interrupt_unknown_thread() {
+        let (started, wait_started) = channel::<()>();
+        let (checked, wait_checked) = channel::<()>();
+        let worker = std::thread::spawn(move || {
+            wait_started.recv().unwrap();
+            is_cancelled!()?;
+            checked.send(()).unwrap();
+            loop {
+                is_cancelled!()?;
+                std::thread::yield_now();
+            }
+        });
+
+        // The thread has not checked cancellation yet, so the interrupt is lost.
+        assert!(!interrupt(worker.thread().id()));
+        started.send(()).unwrap();
+        wait_checked.recv().unwrap();
+        assert!(interrupt(worker.thread().id()));
+        let result: Cancellable<()> = worker.join().unwrap();
+        assert_eq!(result.unwrap_err().cause(), INTERRUPTED_CAUSE);
+    }
+
+    #[test]
+    fn interrupt_never() {
+        assert!(is_cancelled!().is_ok());
+        assert!(interrupt(std::thread::current().id()));
+        // The interrupt flag does not apply in `never` scopes, including snapshots taken there.
+        let inner: Cancellable<_> = crate::never(|| {
+            is_cancelled!()?;
+            Ok(crate::active_triggers())
+        });
+        let inner = inner.unwrap();
+        assert!(is_cancelled!(inner).is_ok());
+        assert_eq!(is_cancelled!().unwrap_err().cause(), INTERRUPTED_CAUSE);
+        assert!(clear_interrupt());
+        assert!(is_cancelled!().is_ok());
+    }
+}
@@ -1,8 +1,6 @@
-use crate::{CancellationTrigger, Cancelled};
-use log::trace;
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering::SeqCst;
+use crate::{
+    CancelOnMetric, CancellationTrigger, Cancelled, MetricLimit, SamplingPolicy, TriggerListener,
+};
 
 /// Run the given `action`, cancelling it using [`CancelMemory`] if the overall memory consumption
 /// of the whole process exceeds the given memory `limit` (in bytes).
@@ -50,42 +48,46 @@ where
 /// overhead to cancellation checks. We are trying to mitigate this by using the "faster" but
 /// less accurate memory check method, but this can still be non-trivial.
 ///
+/// Internally, this is a [`CancelOnMetric`] trigger using [`SamplingPolicy::OnCheck`]. Once
+/// canceled, the observed memory consumption is reported through [`Cancelled::detail`].
+///
 /// See also [`on_memory`].
 ///
 /// ## Logging
-///  - Each trigger should produce a [`trace`] message when actually canceled.
+///  - Each trigger should produce a [`log::trace`] message when actually canceled.
 #[derive(Debug, Clone)]
-pub struct CancelMemory(usize, Arc<AtomicBool>);
+pub struct CancelMemory(CancelOnMetric);
 
 impl CancellationTrigger for CancelMemory {
     fn is_cancelled(&self) -> bool {
-        if self.1.load(SeqCst) {
-            // The trigger is already canceled.
-            return true;
-        }
-
-        if let Some(stats) = memory_stats::memory_stats()
-            && stats.physical_mem > self.0
-        {
-            self.1.store(true, SeqCst); // Remember that this trigger is now canceled.
-            trace!(
-                "`CancelMemory[{:p}]` canceled (limit: {}; used: {}).",
-                self, self.0, stats.physical_mem
-            );
-            return true;
-        }
-
-        false
+        self.0.is_cancelled()
     }
 
     fn type_name(&self) -> &'static str {
         "CancelMemory"
     }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.0.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.0.detail()
+    }
 }
 
 impl CancelMemory {
     /// Create a new instance of [`CancelMemory`] with the given memory limit (in bytes).
     pub fn limit(limit: usize) -> CancelMemory {
-        CancelMemory(limit, Default::default())
+        CancelMemory(CancelOnMetric::new(
+            "CancelMemory",
+            || memory_stats::memory_stats().map(|stats| stats.physical_mem),
+            MetricLimit::Above(limit),
+            SamplingPolicy::OnCheck,
+        ))
     }
 }
@@ -0,0 +1,399 @@
+use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerListener};
+use log::{trace, warn};
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::sync::{Arc, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Run the given `action`, cancelling it if the provided [`CancelOnMetric`] `trigger` observes
+/// a metric value that violates its limit.
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # use cancel_this::{is_cancelled, CancelOnMetric, Cancelled, MetricLimit, SamplingPolicy};
+/// # let _ = env_logger::builder().is_test(true).try_init();
+/// // A "queue" that grows with every processed item.
+/// let queue_depth = Arc::new(AtomicUsize::new(0));
+///
+/// let depth = queue_depth.clone();
+/// let trigger = CancelOnMetric::new(
+///     "CancelQueueDepth",
+///     move || Some(depth.load(Ordering::SeqCst)),
+///     MetricLimit::Above(10),
+///     SamplingPolicy::OnCheck,
+/// );
+///
+/// let result: Result<(), Cancelled> = cancel_this::on_metric(trigger, || {
+///     loop {
+///         is_cancelled!()?;
+///         queue_depth.fetch_add(1, Ordering::SeqCst);
+///     }
+/// });
+///
+/// let error: Cancelled = result.unwrap_err();
+/// assert_eq!(error.cause(), "CancelQueueDepth");
+/// assert_eq!(error.detail(), Some("observed 11 (limit: > 10)"));
+/// ```
+pub fn on_metric<TResult, TError, TAction>(
+    trigger: CancelOnMetric,
+    action: TAction,
+) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(trigger, action)
+}
+
+/// Describes when a sampled metric violates its limit (see [`CancelOnMetric`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricLimit<T> {
+    /// The limit is violated once the observed value is strictly greater than the given value.
+    Above(T),
+    /// The limit is violated once the observed value is strictly smaller than the given value.
+    Below(T),
+}
+
+impl<T: PartialOrd> MetricLimit<T> {
+    /// Returns true if the `observed` value violates this limit.
+    pub fn is_violated_by(&self, observed: &T) -> bool {
+        match self {
+            MetricLimit::Above(limit) => observed > limit,
+            MetricLimit::Below(limit) => observed < limit,
+        }
+    }
+}
+
+impl<T: Display> Display for MetricLimit<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricLimit::Above(limit) => write!(f, "> {limit}"),
+            MetricLimit::Below(limit) => write!(f, "< {limit}"),
+        }
+    }
+}
+
+/// Describes how often the metric of a [`CancelOnMetric`] trigger is sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingPolicy {
+    /// The metric is sampled on every cancellation check. This is the most accurate,
+    /// but also the most expensive option.
+    OnCheck,
+    /// The metric is sampled on cancellation check, but at most once per the given
+    /// [`Duration`]. Other checks only observe the result of the most recent sample.
+    Debounced(Duration),
+    /// The metric is sampled periodically by a background thread. Cancellation checks
+    /// themselves never sample the metric and are thus as cheap as [`CancelAtomic`].
+    Background(Duration),
+}
+
+/// Implementation of [`CancellationTrigger`] that samples an arbitrary metric (e.g., memory
+/// consumption, number of open file descriptors, or queue depth) and is canceled once the
+/// sampled value violates the given [`MetricLimit`]. Once canceled, the trigger stays canceled
+/// and reports the offending value through [`Cancelled::detail`].
+///
+/// The trigger is built from a sampling function (returning `None` when the metric is
+/// not available), a [`MetricLimit`] and a [`SamplingPolicy`]. Since the trigger is a "generic"
+/// implementation of several conceptually different triggers, it also requires
+/// a type name that is reported as [`Cancelled::cause`].
+///
+/// See also [`on_metric`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled due to a limit violation.
+///  - `[warn]` If the trigger is dropped, but the background sampling thread cannot be
+///    safely destroyed.
+#[derive(Debug, Clone)]
+pub struct CancelOnMetric {
+    state: Arc<MetricState>,
+    policy: SamplingPolicy,
+    // The sampler is never accessed. It only needs to be dropped once all copies
+    // of the trigger are destroyed.
+    #[allow(dead_code)]
+    sampler: Option<Arc<MetricSampler>>,
+}
+
+impl CancellationTrigger for CancelOnMetric {
+    fn is_cancelled(&self) -> bool {
+        if self.state.trigger.is_cancelled() {
+            // The trigger is already canceled.
+            return true;
+        }
+
+        match self.policy {
+            SamplingPolicy::OnCheck => self.state.sample(),
+            SamplingPolicy::Debounced(period) => self.state.sample_debounced(period),
+            SamplingPolicy::Background(_) => false,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.state.name
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        matches!(self.policy, SamplingPolicy::Background(_))
+            && self.state.trigger.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.state.trigger.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.state.observed.get().cloned()
+    }
+}
+
+impl CancelOnMetric {
+    /// Create a new [`CancelOnMetric`] trigger which reports cancellation using the given
+    /// `name`, obtains metric values using `sample`, and is canceled once the sampled value
+    /// violates `limit`. The metric is sampled according to the given `policy`.
+    pub fn new<T, TSample>(
+        name: &'static str,
+        sample: TSample,
+        limit: MetricLimit<T>,
+        policy: SamplingPolicy,
+    ) -> Self
+    where
+        T: PartialOrd + Display + Send + Sync + 'static,
+        TSample: Fn() -> Option<T> + Send + Sync + 'static,
+    {
+        let probe = move || {
+            let observed = sample()?;
+            if limit.is_violated_by(&observed) {
+                Some(format!("observed {observed} (limit: {limit})"))
+            } else {
+                None
+            }
+        };
+        let state = Arc::new(MetricState {
+            name,
+            trigger: CancelAtomic::default(),
+            observed: OnceLock::new(),
+            probe: Box::new(probe),
+            created: Instant::now(),
+            last_sample: AtomicU64::new(NEVER_SAMPLED),
+        });
+        let sampler = match policy {
+            SamplingPolicy::Background(period) => {
+                Some(Arc::new(MetricSampler::start(state.clone(), period)))
+            }
+            _ => None,
+        };
+        CancelOnMetric {
+            state,
+            policy,
+            sampler,
+        }
+    }
+}
+
+/// Marks a [`MetricState`] that has not been sampled yet.
+const NEVER_SAMPLED: u64 = u64::MAX;
+
+/// The state shared by all copies of a [`CancelOnMetric`] trigger (and its background sampler).
+struct MetricState {
+    name: &'static str,
+    trigger: CancelAtomic,
+    observed: OnceLock<String>,
+    probe: Box<dyn Fn() -> Option<String> + Send + Sync>,
+    created: Instant,
+    /// Time of the last sample (in nanoseconds since `created`), or [`NEVER_SAMPLED`].
+    last_sample: AtomicU64,
+}
+
+impl Debug for MetricState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetricState")
+            .field("name", &self.name)
+            .field("trigger", &self.trigger)
+            .field("observed", &self.observed)
+            .finish()
+    }
+}
+
+impl MetricState {
+    /// Sample the metric and cancel the trigger if the limit is violated.
+    fn sample(&self) -> bool {
+        match (self.probe)() {
+            None => false,
+            Some(observed) => {
+                trace!(
+                    "`{}[{:p}]` canceled ({}).",
+                    self.name,
+                    self.trigger.id_ref(),
+                    observed
+                );
+                // Only the first violation is reported, even if multiple threads
+                // observe the violation at the same time.
+                let _ = self.observed.set(observed);
+                self.trigger.cancel();
+                true
+            }
+        }
+    }
+
+    /// Sample the metric, but only if the last sample is older than `period`.
+    fn sample_debounced(&self, period: Duration) -> bool {
+        let now = u64::try_from(self.created.elapsed().as_nanos()).unwrap_or(u64::MAX - 1);
+        let last = self.last_sample.load(Ordering::Relaxed);
+        let is_due =
+            last == NEVER_SAMPLED || u128::from(now.saturating_sub(last)) >= period.as_nanos();
+        // If multiple threads see the sample as due, only one of them actually samples.
+        if is_due
+            && self
+                .last_sample
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.sample()
+        } else {
+            false
+        }
+    }
+}
+
+/// An internal data structure that manages the background thread used by
+/// [`SamplingPolicy::Background`]. Similar to the timer of [`crate::CancelTimer`], the thread
+/// is safely shut down once the trigger is no longer needed.
+#[derive(Debug)]
+struct MetricSampler {
+    state: Arc<MetricState>,
+    sampler_thread: Option<JoinHandle<()>>,
+    stop_sampler: Sender<()>,
+}
+
+impl MetricSampler {
+    pub fn start(state: Arc<MetricState>, period: Duration) -> Self {
+        let thread_state = state.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            loop {
+                match receiver.recv_timeout(period) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        if thread_state.sample() {
+                            // Once canceled, there is no reason to keep sampling.
+                            return;
+                        }
+                    }
+                    // The trigger has been dropped.
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        });
+        MetricSampler {
+            state,
+            sampler_thread: Some(handle),
+            stop_sampler: sender,
+        }
+    }
+}
+
+impl Drop for MetricSampler {
+    fn drop(&mut self) {
+        let thread = self
+            .sampler_thread
+            .take()
+            .expect("Invariant violation: Sampler thread removed before drop.");
+
+        let join = match self.stop_sampler.send(()) {
+            Ok(()) => thread.join(),
+            Err(_) => {
+                // The receiver has already been deallocated, meaning the trigger is most
+                // likely canceled and the thread should be dead.
+                if !thread.is_finished() {
+                    warn!(
+                        "Sampler of `{}[{:p}]` cannot be stopped. Possible thread leak.`",
+                        self.state.name,
+                        self.state.trigger.id_ref()
+                    );
+                    return;
+                } else {
+                    thread.join()
+                }
+            }
+        };
+        if join.is_err() {
+            // The thread panicked, meaning we probably want to propagate it.
+            panic!("Sampler thread of `{}` trigger panicked.", self.state.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancelOnMetric, CancellationTrigger, MetricLimit, SamplingPolicy};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Create a metric that counts how many times it has been sampled
+    /// and reports a fixed value.
+    fn counting_metric(
+        value: &Arc<AtomicUsize>,
+        samples: &Arc<AtomicUsize>,
+    ) -> impl Fn() -> Option<usize> + Send + Sync + 'static {
+        let value = value.clone();
+        let samples = samples.clone();
+        move || {
+            samples.fetch_add(1, Ordering::SeqCst);
+            Some(value.load(Ordering::SeqCst))
+        }
+    }
+
+    #[test]
+    fn metric_debounced() {
+        let value = Arc::new(AtomicUsize::new(5));
+        let samples = Arc::new(AtomicUsize::new(0));
+        let trigger = CancelOnMetric::new(
+            "CancelTest",
+            counting_metric(&value, &samples),
+            MetricLimit::Below(3),
+            SamplingPolicy::Debounced(Duration::from_secs(60)),
+        );
+
+        // Only the first check actually samples the metric.
+        for _ in 0..10 {
+            assert!(!trigger.is_cancelled());
+        }
+        assert_eq!(samples.load(Ordering::SeqCst), 1);
+
+        // The violation is not observed until the next sample is due.
+        value.store(1, Ordering::SeqCst);
+        assert!(!trigger.is_cancelled());
+        assert_eq!(trigger.detail(), None);
+    }
+
+    #[test]
+    fn metric_background() {
+        let value = Arc::new(AtomicUsize::new(5));
+        let samples = Arc::new(AtomicUsize::new(0));
+        let trigger = CancelOnMetric::new(
+            "CancelTest",
+            counting_metric(&value, &samples),
+            MetricLimit::Above(10),
+            SamplingPolicy::Background(Duration::from_millis(5)),
+        );
+
+        assert!(!trigger.is_cancelled());
+        value.store(20, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(trigger.is_cancelled());
+        assert_eq!(trigger.type_name(), "CancelTest");
+        assert_eq!(
+            trigger.detail().as_deref(),
+            Some("observed 20 (limit: > 10)")
+        );
+
+        // Once canceled, the value is latched and the metric is no longer sampled.
+        let sampled = samples.load(Ordering::SeqCst);
+        value.store(0, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(trigger.is_cancelled());
+        assert_eq!(samples.load(Ordering::SeqCst), sampled);
+    }
+}
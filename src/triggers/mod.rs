@@ -1,4 +1,7 @@
+use crate::Cancelled;
 use dyn_clone::{DynClone, clone_trait_object};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 mod timer;
 pub use timer::*;
@@ -12,15 +15,62 @@ pub use never::*;
 mod atomic;
 pub use atomic::*;
 
+mod metric;
+pub use metric::*;
+
+mod global;
+pub use global::{SHUTDOWN_CAUSE, install_global, shutdown, uninstall_global};
+pub(crate) use global::{check_with_globals, has_globals, subscribe_globals, unsubscribe_globals};
+
+mod snapshot;
+pub use snapshot::TriggerSnapshot;
+
+mod interrupt;
+pub(crate) use interrupt::{CancelInterrupt, check_interrupt, suspend_interrupt};
+pub use interrupt::{INTERRUPTED_CAUSE, clear_interrupt, interrupt};
+
 #[cfg(feature = "memory")]
 mod memory;
 #[cfg(feature = "memory")]
 pub use memory::*;
 
+/// Cancellation using `SIGINT` (Ctrl+C), including [`ctrlc::notify_sigint`] for applications
+/// that install their own handler.
 #[cfg(feature = "ctrlc")]
-mod ctrlc;
+pub mod ctrlc;
 #[cfg(feature = "ctrlc")]
-pub use ctrlc::*;
+pub use ctrlc::{CancelCtrlc, on_sigint, try_on_sigint};
+
+#[cfg(all(feature = "signals", unix))]
+mod signal;
+#[cfg(all(feature = "signals", unix))]
+pub use signal::*;
+
+/// A shared background thread that observes file descriptors and periodic probes on behalf
+/// of OS-based triggers. Checking such triggers is just an atomic load, since all the actual
+/// work happens on the watcher thread.
+#[cfg(all(any(feature = "process", feature = "file", feature = "stdio"), unix))]
+mod watcher;
+
+#[cfg(all(feature = "process", unix))]
+mod process;
+#[cfg(all(feature = "process", unix))]
+pub use process::*;
+
+#[cfg(all(feature = "file", unix))]
+mod file;
+#[cfg(all(feature = "file", unix))]
+pub use file::*;
+
+#[cfg(all(feature = "stdio", unix))]
+mod stdio;
+#[cfg(all(feature = "stdio", unix))]
+pub use stdio::*;
+
+#[cfg(all(feature = "control", unix))]
+mod control;
+#[cfg(all(feature = "control", unix))]
+pub use control::*;
 
 #[cfg(feature = "pyo3")]
 mod pyo3;
@@ -45,10 +95,93 @@ pub trait CancellationTrigger: Send + Sync + DynClone {
     /// Return the type name of this [`CancellationTrigger`], or in case of "composite"
     /// triggers, *the type name of the trigger that actually signaled the cancellation*.
     fn type_name(&self) -> &'static str;
+
+    /// Return additional human-readable information about the cancellation (e.g., the value
+    /// that caused it), or in case of "composite" triggers, *the detail of the trigger that
+    /// actually signaled the cancellation*.
+    ///
+    /// The value is only meaningful once the trigger is canceled. By default, no detail
+    /// is reported.
+    fn detail(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the [`Cancelled`] error describing the cause of cancellation if this trigger
+    /// is canceled, or `None` otherwise.
+    ///
+    /// Unlike calling [`CancellationTrigger::is_cancelled`] and then
+    /// [`CancellationTrigger::type_name`], this evaluates the trigger (and in case of "composite"
+    /// triggers, each nested trigger) at most once. The default implementation is based
+    /// on the other methods, which is sufficient for triggers that are not composed of other
+    /// triggers and report their detail without re-evaluating the cancellation condition.
+    fn check(&self) -> Option<Cancelled> {
+        if self.is_cancelled() {
+            Some(Cancelled::with_optional_detail(
+                self.type_name(),
+                self.detail(),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Register the `listener` to be notified (see [`TriggerListener::notify`]) once this
+    /// trigger is canceled. If the trigger is already canceled, the listener is notified
+    /// immediately. Returns `false` if the trigger cannot notify listeners, meaning it has to be
+    /// checked explicitly every time.
+    ///
+    /// This allows [`CancelChain`] to skip checking individual triggers until one of them
+    /// is actually canceled. By default, subscriptions are not supported. "Composite" triggers
+    /// should forward the listener to all nested triggers, and only return `true` if all of them
+    /// support subscriptions.
+    ///
+    /// Subscriptions are managed while the thread-local triggers are being updated, hence
+    /// this method (and [`CancellationTrigger::unsubscribe`]) must not check thread-local
+    /// cancellation or enter new cancellation scopes.
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        let _ = listener;
+        false
+    }
+
+    /// Remove the `listener` previously registered by [`CancellationTrigger::subscribe`].
+    /// If the same listener has been registered multiple times, only one registration
+    /// is removed.
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        let _ = listener;
+    }
 }
 
 clone_trait_object!(CancellationTrigger);
 
+/// A flag shared between a [`CancelChain`] and its triggers, which is set once any
+/// of the triggers is canceled (see [`CancellationTrigger::subscribe`]).
+#[derive(Debug, Clone, Default)]
+pub struct TriggerListener(Arc<AtomicBool>);
+
+impl TriggerListener {
+    /// Notify the listener that the trigger it is subscribed to has been canceled. This must
+    /// be called *after* the trigger is canceled.
+    pub fn notify(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// True if this listener and `other` are the same object (i.e., one is a copy
+    /// of the other).
+    pub fn same_as(&self, other: &TriggerListener) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub(crate) fn is_notified(&self) -> bool {
+        // Pairs with the release store in `notify`, so that the canceled state of the trigger
+        // is visible once the notification is observed.
+        self.0.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
 /// A dynamic boxed [`CancellationTrigger`].
 pub type DynamicCancellationTrigger = Box<dyn CancellationTrigger>;
 
@@ -60,4 +193,41 @@ impl CancellationTrigger for DynamicCancellationTrigger {
     fn type_name(&self) -> &'static str {
         self.as_ref().type_name()
     }
+
+    fn detail(&self) -> Option<String> {
+        self.as_ref().detail()
+    }
+
+    fn check(&self) -> Option<Cancelled> {
+        self.as_ref().check()
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.as_ref().subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.as_ref().unsubscribe(listener)
+    }
+}
+
+/// Wait until the trigger is canceled, or fail after a generous timeout. Used by the tests
+/// of triggers that are canceled asynchronously (e.g., by a signal or the watcher thread).
+#[cfg(all(
+    test,
+    unix,
+    any(
+        feature = "ctrlc",
+        feature = "signals",
+        feature = "process",
+        feature = "file",
+        feature = "stdio"
+    )
+))]
+pub(crate) fn wait_for<T: CancellationTrigger>(trigger: &T) {
+    let start = std::time::Instant::now();
+    while !trigger.is_cancelled() {
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
 }
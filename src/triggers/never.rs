@@ -1,5 +1,9 @@
-use crate::liveness::LivenessInterceptor;
-use crate::{CancelChain, CancellationTrigger, Cancelled, TRIGGER};
+use crate::triggers::suspend_interrupt;
+use crate::{
+    CancellationTrigger, Cancelled, TRIGGER, TriggerListener, new_thread_chain,
+    refresh_thread_triggers,
+};
+use std::rc::Rc;
 
 /// Run the given `action` by overriding current cancellation criteria with [`CancelNever`],
 /// meaning they do not apply and the action is never canceled.
@@ -42,10 +46,15 @@ where
     TAction: FnOnce() -> Result<TResult, TError>,
     TError: From<Cancelled>,
 {
-    let mut set_aside = LivenessInterceptor::<CancelChain>::default();
+    // The interrupt flag of the thread does not apply either.
+    let suspended = suspend_interrupt();
+    let mut set_aside = Rc::new(new_thread_chain());
     TRIGGER.with_borrow_mut(|value| std::mem::swap(value, &mut set_aside));
     let result = crate::on_trigger(CancelNever, action);
     TRIGGER.with_borrow_mut(|value| std::mem::swap(value, &mut set_aside));
+    drop(suspended);
+    // The restored triggers (or the interrupt flag) could have been canceled in the meantime.
+    refresh_thread_triggers();
     result
 }
 
@@ -63,4 +72,9 @@ impl CancellationTrigger for CancelNever {
     fn type_name(&self) -> &'static str {
         "CancelNever"
     }
+
+    fn subscribe(&self, _listener: &TriggerListener) -> bool {
+        // The trigger is never canceled, so there is nothing to notify.
+        true
+    }
 }
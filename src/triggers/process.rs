@@ -0,0 +1,512 @@
+use crate::triggers::watcher::{Watch, WatchTrigger, register_watch};
+use crate::{CancellationTrigger, Cancelled, TriggerListener};
+use std::time::Duration;
+
+/// Run the given `action`, cancelling it using [`CancelParentDeath`] if the parent
+/// process of this process terminates.
+///
+/// This is typically used by worker processes that should stop computing once
+/// the supervisor that spawned them dies.
+///
+/// # Panics
+/// The operation panics if the parent process cannot be observed
+/// (see [`CancelParentDeath::try_new`]).
+pub fn on_parent_death<TResult, TError, TAction>(action: TAction) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(CancelParentDeath::new(), action)
+}
+
+/// Run the given `action`, cancelling it using [`CancelPidExit`] if the process with
+/// the given `pid` terminates.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, is_cancelled};
+/// # let _ = env_logger::builder().is_test(true).try_init();
+/// fn cancellable_counter(count: usize) -> Result<(), Cancelled> {
+///     for _ in 0..count {
+///         is_cancelled!()?;
+///         std::thread::sleep(Duration::from_millis(10));
+///     }
+///     Ok(())
+/// }
+///
+/// let mut child = std::process::Command::new("sleep").arg("0.1").spawn().unwrap();
+/// let pid = child.id();
+///
+/// // The first action is fast (30ms) and should complete before the child exits.
+/// let result_fast = cancel_this::on_pid_exit(pid, || cancellable_counter(3));
+/// assert!(result_fast.is_ok());
+///
+/// // The second action is slow and will be canceled once the child exits.
+/// let result_slow = cancel_this::on_pid_exit(pid, || cancellable_counter(500));
+/// let error = result_slow.unwrap_err();
+/// assert_eq!(error.detail(), Some(format!("process {pid} exited").as_str()));
+///
+/// child.wait().unwrap();
+/// ```
+///
+/// # Panics
+/// The operation panics if the process cannot be observed (see [`CancelPidExit::try_new`]).
+pub fn on_pid_exit<TResult, TError, TAction>(pid: u32, action: TAction) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(CancelPidExit::new(pid), action)
+}
+
+/// The polling interval used on platforms where process termination cannot be observed
+/// directly (i.e., without `pidfd_open`).
+const PROCESS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Implementation of [`CancellationTrigger`] that is canceled once the process with the given
+/// PID terminates. The PID is reported through [`Cancelled::detail`].
+///
+/// On Linux, the process is observed using `pidfd_open`, which reports termination
+/// immediately (including child processes that have not been reaped yet). On other
+/// platforms (or older kernels), the existence of the process is polled periodically using
+/// `kill(pid, 0)`. In such case, child processes are only considered terminated once they are
+/// reaped. In both cases, the actual observation is performed by a shared watcher thread,
+/// meaning cancellation checks are as cheap as [`crate::CancelAtomic`].
+///
+/// See also [`on_pid_exit`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled.
+#[derive(Debug, Clone)]
+pub struct CancelPidExit {
+    pid: u32,
+    watch: WatchTrigger,
+}
+
+impl CancellationTrigger for CancelPidExit {
+    fn is_cancelled(&self) -> bool {
+        self.watch.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelPidExit"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.watch.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.watch.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.watch.detail()
+    }
+}
+
+impl CancelPidExit {
+    /// Create a new [`CancelPidExit`] for the process with the given `pid`. If such process
+    /// does not exist, the trigger is canceled immediately.
+    ///
+    /// # Panics
+    /// The operation panics if the process cannot be observed (see [`CancelPidExit::try_new`]).
+    pub fn new(pid: u32) -> Self {
+        Self::try_new(pid).unwrap()
+    }
+
+    /// Try to create a new [`CancelPidExit`] for the process with the given `pid`, returning
+    /// an error if the `pid` is not valid or the shared watcher thread cannot be started.
+    pub fn try_new(pid: u32) -> Result<Self, std::io::Error> {
+        let pid_t = to_pid_t(pid)?;
+        let watch = watch_process(
+            "CancelPidExit",
+            pid_t,
+            format!("process {pid} exited"),
+            move || process_exists(pid_t),
+        )?;
+        Ok(CancelPidExit { pid, watch })
+    }
+
+    /// The PID of the observed process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+}
+
+/// Implementation of [`CancellationTrigger`] that is canceled once the parent process
+/// of this process terminates. The PID of the parent is reported through [`Cancelled::detail`].
+///
+/// The parent is observed the same way as in [`CancelPidExit`]. When `pidfd_open` is not
+/// available, the trigger is canceled once the parent PID of this process changes (i.e.,
+/// this process is "re-parented" to `init` or a sub-reaper). We intentionally do not
+/// use `prctl(PR_SET_PDEATHSIG)`, because it consumes a process-wide signal and is tied to
+/// the termination of the parent *thread* that spawned this process.
+///
+/// Note that [`CancelParentDeath::new`] observes the parent at the time the trigger is
+/// created. If the original parent has already terminated by then, this process is already
+/// re-parented and the trigger observes the adoptive parent instead (typically `init`, i.e.,
+/// it is never canceled). If the PID of the original parent is known (e.g., the supervisor
+/// passes it to the worker), use [`CancelParentDeath::with_parent`] instead, which is
+/// canceled immediately if the process is no longer a child of the given parent.
+///
+/// See also [`on_parent_death`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled.
+#[derive(Debug, Clone)]
+pub struct CancelParentDeath {
+    pid: u32,
+    watch: WatchTrigger,
+}
+
+impl CancellationTrigger for CancelParentDeath {
+    fn is_cancelled(&self) -> bool {
+        self.watch.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelParentDeath"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.watch.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.watch.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.watch.detail()
+    }
+}
+
+impl Default for CancelParentDeath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelParentDeath {
+    /// Create a new [`CancelParentDeath`] for the current parent process.
+    ///
+    /// # Panics
+    /// The operation panics if the parent process cannot be observed
+    /// (see [`CancelParentDeath::try_new`]).
+    pub fn new() -> Self {
+        Self::try_new().unwrap()
+    }
+
+    /// Try to create a new [`CancelParentDeath`], returning an error if the shared watcher
+    /// thread cannot be started.
+    pub fn try_new() -> Result<Self, std::io::Error> {
+        let parent = unsafe { libc::getppid() };
+        Self::watch_parent(parent)
+    }
+
+    /// Create a new [`CancelParentDeath`] for the given original `parent` process. If the
+    /// parent of this process is not `parent` (i.e., the original parent already terminated),
+    /// the trigger is canceled immediately.
+    ///
+    /// # Panics
+    /// The operation panics if the parent process cannot be observed
+    /// (see [`CancelParentDeath::try_with_parent`]).
+    pub fn with_parent(parent: u32) -> Self {
+        Self::try_with_parent(parent).unwrap()
+    }
+
+    /// Try to create a new [`CancelParentDeath`] for the given original `parent` process,
+    /// returning an error if the `parent` is not valid or the shared watcher thread cannot
+    /// be started.
+    pub fn try_with_parent(parent: u32) -> Result<Self, std::io::Error> {
+        Self::watch_parent(to_pid_t(parent)?)
+    }
+
+    /// Create the trigger, assuming the given `parent` is the original parent process.
+    fn watch_parent(parent: libc::pid_t) -> Result<Self, std::io::Error> {
+        let is_parent = move || unsafe { libc::getppid() } == parent;
+        let watch = watch_process(
+            "CancelParentDeath",
+            parent,
+            format!("parent process {parent} exited"),
+            is_parent,
+        )?;
+        Ok(CancelParentDeath {
+            pid: parent as u32,
+            watch,
+        })
+    }
+
+    /// The PID of the observed parent process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+}
+
+/// Register a watch that reports the termination of the given process. The `is_alive` test
+/// is used to detect termination if the process cannot be observed directly.
+fn watch_process<F>(
+    name: &'static str,
+    pid: libc::pid_t,
+    detail: String,
+    is_alive: F,
+) -> Result<WatchTrigger, std::io::Error>
+where
+    F: Fn() -> bool + Send + 'static,
+{
+    #[cfg(target_os = "linux")]
+    if let Some(pidfd) = open_pidfd(pid) {
+        use std::os::fd::AsRawFd;
+        let fd = pidfd.as_raw_fd();
+        let probe_detail = detail.clone();
+        let watch = register_watch(
+            name,
+            Watch {
+                fd: Some((fd, libc::POLLIN)),
+                interval: None,
+                // The descriptor is owned by the probe, so it is closed once the watch is removed.
+                probe: Box::new(move |_| {
+                    let _ = &pidfd;
+                    Some(probe_detail.clone())
+                }),
+            },
+        )?;
+        // The process could have terminated before the descriptor was opened
+        // (e.g., parent process could have been replaced by a different process).
+        if !is_alive() {
+            watch.cancel(detail);
+        }
+        return Ok(watch);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = pid;
+
+    let initially_alive = is_alive();
+    let probe_detail = detail.clone();
+    let watch = register_watch(
+        name,
+        Watch {
+            fd: None,
+            interval: Some(PROCESS_POLL_INTERVAL),
+            probe: Box::new(move |_| (!is_alive()).then(|| probe_detail.clone())),
+        },
+    )?;
+    if !initially_alive {
+        watch.cancel(detail);
+    }
+    Ok(watch)
+}
+
+/// Open a `pidfd` for the given process, or return `None` if this is not possible
+/// (e.g., the kernel does not support `pidfd_open`, or the process does not exist).
+#[cfg(target_os = "linux")]
+fn open_pidfd(pid: libc::pid_t) -> Option<std::os::fd::OwnedFd> {
+    use std::os::fd::FromRawFd;
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 {
+        None
+    } else {
+        Some(unsafe { std::os::fd::OwnedFd::from_raw_fd(fd as libc::c_int) })
+    }
+}
+
+/// Returns true if a process with the given PID exists.
+fn process_exists(pid: libc::pid_t) -> bool {
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // The process exists, but we are not allowed to send signals to it.
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+fn to_pid_t(pid: u32) -> Result<libc::pid_t, std::io::Error> {
+    match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => Ok(pid),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid process id: {pid}."),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::triggers::wait_for;
+    use crate::{CancelParentDeath, CancelPidExit, CancellationTrigger};
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::time::{Duration, Instant};
+
+    /// Fork a child process which runs until it is killed (or exits immediately if `exit`).
+    /// Only async-signal-safe functions can be used in a fork of a multithreaded process.
+    fn fork_child(exit: bool) -> libc::pid_t {
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            unsafe {
+                if !exit {
+                    loop {
+                        libc::pause();
+                    }
+                }
+                libc::_exit(0);
+            }
+        }
+        pid
+    }
+
+    fn reap(pid: libc::pid_t) {
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+    }
+
+    fn pipe() -> [libc::c_int; 2] {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        fds
+    }
+
+    fn to_c_string(value: &[u8]) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    #[test]
+    fn pid_exit_child() {
+        let child = fork_child(false);
+        let trigger = CancelPidExit::new(child as u32);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!trigger.is_cancelled());
+
+        assert_eq!(unsafe { libc::kill(child, libc::SIGKILL) }, 0);
+        reap(child);
+        wait_for(&trigger);
+        assert_eq!(trigger.detail(), Some(format!("process {child} exited")));
+
+        // A process that no longer exists cancels the trigger immediately.
+        assert!(CancelPidExit::new(child as u32).is_cancelled());
+        assert!(CancelPidExit::try_new(0).is_err());
+    }
+
+    #[test]
+    fn parent_death() {
+        let parent = CancelParentDeath::new();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!parent.is_cancelled());
+        assert_eq!(parent.pid(), unsafe { libc::getppid() } as u32);
+
+        // If the original parent is not our parent anymore, the trigger is canceled.
+        let child = fork_child(true);
+        let replaced = CancelParentDeath::with_parent(child as u32);
+        wait_for(&replaced);
+        assert_eq!(
+            replaced.detail(),
+            Some(format!("parent process {child} exited"))
+        );
+        reap(child);
+        assert!(CancelParentDeath::try_with_parent(0).is_err());
+    }
+
+    /// The pipes used by [`parent_death_grandchild`] (ready and result), if it runs
+    /// as a grandchild of [`parent_death_fork`].
+    const GRANDCHILD_PIPES: &str = "CANCEL_THIS_TEST_GRANDCHILD_PIPES";
+
+    #[test]
+    fn parent_death_fork() {
+        // The test forks an intermediate process, which forks a grandchild watching it and
+        // then exits. The grandchild runs `parent_death_grandchild` in a fresh copy of the test
+        // binary, since the fork of a multithreaded process cannot safely start new threads.
+        let [ready_read, ready_write] = pipe();
+        let [result_read, result_write] = pipe();
+        let exe = std::env::current_exe().unwrap();
+        let exe = to_c_string(exe.as_os_str().as_bytes());
+        let args = [
+            "--exact",
+            "triggers::process::tests::parent_death_grandchild",
+            "--ignored",
+            "--test-threads=1",
+        ]
+        .map(|it| to_c_string(it.as_bytes()));
+        let mut argv = vec![exe.as_ptr()];
+        argv.extend(args.iter().map(|it| it.as_ptr()));
+        argv.push(std::ptr::null());
+        let mut env = std::env::vars_os()
+            .map(|(key, value)| {
+                let mut variable = key.as_bytes().to_vec();
+                variable.push(b'=');
+                variable.extend(value.as_bytes());
+                to_c_string(&variable)
+            })
+            .collect::<Vec<_>>();
+        let pipes = format!("{GRANDCHILD_PIPES}={ready_write},{result_write}");
+        env.push(to_c_string(pipes.as_bytes()));
+        let mut envp = env.iter().map(|it| it.as_ptr()).collect::<Vec<_>>();
+        envp.push(std::ptr::null());
+        let null = unsafe { libc::open(c"/dev/null".as_ptr(), libc::O_WRONLY) };
+        assert!(null >= 0);
+
+        let intermediate = unsafe { libc::fork() };
+        assert!(intermediate >= 0);
+        if intermediate == 0 {
+            // Only async-signal-safe functions from here on.
+            unsafe {
+                if libc::fork() == 0 {
+                    libc::dup2(null, libc::STDOUT_FILENO);
+                    libc::execve(exe.as_ptr(), argv.as_ptr(), envp.as_ptr());
+                    libc::_exit(127);
+                }
+                // Exit once the grandchild observes this process (or fails to start).
+                libc::close(ready_write);
+                let mut byte = 0u8;
+                libc::read(ready_read, (&mut byte as *mut u8).cast(), 1);
+                libc::_exit(0);
+            }
+        }
+        unsafe {
+            libc::close(null);
+            libc::close(ready_read);
+            libc::close(ready_write);
+            libc::close(result_write);
+        }
+
+        let mut poll = libc::pollfd {
+            fd: result_read,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let polled = unsafe { libc::poll(&mut poll, 1, 10_000) };
+        let mut result = 0u8;
+        if polled == 1 {
+            unsafe { libc::read(result_read, (&mut result as *mut u8).cast(), 1) };
+        }
+        unsafe {
+            libc::kill(intermediate, libc::SIGKILL);
+            libc::close(result_read);
+        }
+        reap(intermediate);
+        assert_eq!(
+            result, b'1',
+            "The grandchild did not observe the parent death."
+        );
+    }
+
+    #[test]
+    #[ignore = "only runs as a grandchild process of `parent_death_fork`"]
+    fn parent_death_grandchild() {
+        let pipes = std::env::var(GRANDCHILD_PIPES).expect("Not a part of `parent_death_fork`.");
+        let (ready, result) = pipes.split_once(',').unwrap();
+        let (ready, result) = (
+            ready.parse::<i32>().unwrap(),
+            result.parse::<i32>().unwrap(),
+        );
+        let trigger = CancelParentDeath::new();
+        unsafe { libc::write(ready, b"r".as_ptr().cast(), 1) };
+        let start = Instant::now();
+        while !trigger.is_cancelled() && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let outcome = if trigger.is_cancelled() { b"1" } else { b"0" };
+        unsafe { libc::write(result, outcome.as_ptr().cast(), 1) };
+    }
+}
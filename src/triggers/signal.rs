@@ -0,0 +1,481 @@
+use crate::registration::Registration;
+use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerListener};
+use lazy_static::lazy_static;
+use libc::c_int;
+use log::{trace, warn};
+use std::io::{ErrorKind, Read};
+use std::os::fd::IntoRawFd;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+/// Run the given `action`, cancelling it using [`CancelSignal`] if any of the given `signals`
+/// is received by the process.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use cancel_this::{Cancelled, is_cancelled};
+/// # let _ = env_logger::builder().is_test(true).try_init();
+/// fn cancellable_counter(count: usize) -> Result<(), Cancelled> {
+///     for _ in 0..count {
+///         is_cancelled!()?;
+///         std::thread::sleep(Duration::from_millis(10));
+///     }
+///     Ok(())
+/// }
+///
+/// std::thread::spawn(|| {
+///     // Wait for 100ms and then send SIGUSR1 to this process.
+///     std::thread::sleep(Duration::from_millis(100));
+///     let pid = std::process::id() as libc::pid_t;
+///     unsafe { assert_eq!(libc::kill(pid, libc::SIGUSR1), 0) }
+/// });
+///
+/// let signals = [libc::SIGTERM, libc::SIGUSR1];
+///
+/// // The first action is fast (30ms) and should complete before the signal is sent.
+/// let result_fast = cancel_this::on_signal(&signals, || cancellable_counter(3));
+/// assert!(result_fast.is_ok());
+///
+/// // The second action is slow and will be canceled by the signal.
+/// let result_slow = cancel_this::on_signal(&signals, || cancellable_counter(50));
+/// let error = result_slow.unwrap_err();
+/// assert_eq!(error.cause(), "CancelSignal");
+/// assert_eq!(error.detail(), Some("SIGUSR1"));
+/// ```
+///
+/// # Panics
+/// The operation panics if the signal handler cannot be installed (see [`CancelSignal::try_new`]).
+pub fn on_signal<TResult, TError, TAction>(
+    signals: &[c_int],
+    action: TAction,
+) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(CancelSignal::new(signals), action)
+}
+
+/// Signals are tracked in fixed-size arrays indexed by the signal number. This covers
+/// the standard and real-time signals on all common platforms.
+const SIGNAL_COUNT: usize = 65;
+
+/// Signals that cannot be caught or only report synchronous faults.
+const FORBIDDEN_SIGNALS: [c_int; 6] = [
+    libc::SIGKILL,
+    libc::SIGSTOP,
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGFPE,
+    libc::SIGILL,
+];
+
+/// Set by the signal handler once a signal is received. Reset by the watcher thread.
+static PENDING: [AtomicBool; SIGNAL_COUNT] = [const { AtomicBool::new(false) }; SIGNAL_COUNT];
+
+/// The handler that was installed for each signal before [`handle_signal`].
+static PREVIOUS_HANDLER: [AtomicUsize; SIGNAL_COUNT] =
+    [const { AtomicUsize::new(libc::SIG_DFL) }; SIGNAL_COUNT];
+
+/// True if the corresponding [`PREVIOUS_HANDLER`] expects `siginfo_t` (`SA_SIGINFO`).
+static PREVIOUS_SIGINFO: [AtomicBool; SIGNAL_COUNT] =
+    [const { AtomicBool::new(false) }; SIGNAL_COUNT];
+
+/// Write end of the "self-pipe" used to wake up the watcher thread.
+static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Private global state shared by all [`CancelSignal`] triggers.
+static SIGNAL_STATE: Mutex<SignalState> = Mutex::new(SignalState {
+    next_id: 0,
+    waiting: Vec::new(),
+    installed: Vec::new(),
+});
+
+struct SignalState {
+    /// Identifier assigned to the next registered trigger.
+    next_id: u64,
+    /// Triggers waiting for one of their signals.
+    waiting: Vec<SignalWaiter>,
+    /// Signals that use [`handle_signal`]. Once installed, the handler is never removed.
+    installed: Vec<c_int>,
+}
+
+struct SignalWaiter {
+    id: u64,
+    signals: Vec<c_int>,
+    trigger: CancelAtomic,
+    received: Arc<OnceLock<c_int>>,
+}
+
+fn signal_state() -> MutexGuard<'static, SignalState> {
+    SIGNAL_STATE
+        .lock()
+        .expect("Global state of `CancelSignal` is corrupted.")
+}
+
+lazy_static! {
+    /// The result of the watcher thread initialization, called exactly once before the first
+    /// use of the signal functionality.
+    static ref WATCHER_INITIALIZED: Result<(), std::io::Error> = start_watcher();
+}
+
+/// Start the watcher thread which waits for the signal handler to write into the self-pipe
+/// and then cancels the triggers waiting for the received signals. Once started, the thread
+/// runs until the application is terminated.
+fn start_watcher() -> Result<(), std::io::Error> {
+    let (mut reader, writer) = UnixStream::pair()?;
+    // The signal handler must never block. If the pipe is full, the watcher
+    // is going to wake up anyway.
+    writer.set_nonblocking(true)?;
+    WAKE_FD.store(writer.into_raw_fd(), Ordering::SeqCst);
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 64];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(_) => dispatch_pending(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    warn!("`CancelSignal` watcher cannot read signal notifications: {e:?}");
+                    break;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Cancel all triggers waiting for the signals that are currently pending.
+fn dispatch_pending() {
+    let mut state = signal_state();
+    for (signal, pending) in PENDING.iter().enumerate() {
+        if !pending.swap(false, Ordering::SeqCst) {
+            continue;
+        }
+        let signal = signal as c_int;
+        let total = state
+            .waiting
+            .iter()
+            .filter(|it| it.signals.contains(&signal))
+            .count();
+        trace!(
+            "Received {}. Cancelling triggers ({total} total).",
+            signal_name(signal)
+        );
+        state.waiting.retain(|waiter| {
+            if waiter.signals.contains(&signal) {
+                let _ = waiter.received.set(signal);
+                waiter.trigger.cancel();
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+/// The actual signal handler. It only performs async-signal-safe operations: it marks
+/// the signal as pending, wakes up the watcher thread, and calls the previous handler.
+extern "C" fn handle_signal(signal: c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+    let errno = errno_location();
+    // Writing into the pipe can modify `errno` of the interrupted code.
+    let saved_errno = if errno.is_null() {
+        0
+    } else {
+        unsafe { *errno }
+    };
+
+    if let Some(index) = usize::try_from(signal).ok().filter(|it| *it < SIGNAL_COUNT) {
+        PENDING[index].store(true, Ordering::SeqCst);
+        let fd = WAKE_FD.load(Ordering::SeqCst);
+        if fd >= 0 {
+            let byte = [0u8];
+            unsafe { libc::write(fd, byte.as_ptr().cast(), 1) };
+        }
+
+        // Chain the handler that was installed before this one (e.g., by the `ctrlc` crate).
+        let previous = PREVIOUS_HANDLER[index].load(Ordering::SeqCst);
+        if previous != libc::SIG_DFL && previous != libc::SIG_IGN {
+            if PREVIOUS_SIGINFO[index].load(Ordering::SeqCst) {
+                let previous: extern "C" fn(c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+                    unsafe { std::mem::transmute(previous) };
+                previous(signal, info, context);
+            } else {
+                let previous: extern "C" fn(c_int) = unsafe { std::mem::transmute(previous) };
+                previous(signal);
+            }
+        }
+    }
+
+    if !errno.is_null() {
+        unsafe { *errno = saved_errno };
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "emscripten"))]
+fn errno_location() -> *mut c_int {
+    unsafe { libc::__errno_location() }
+}
+
+#[cfg(target_os = "android")]
+fn errno_location() -> *mut c_int {
+    unsafe { libc::__errno() }
+}
+
+#[cfg(any(target_vendor = "apple", target_os = "freebsd"))]
+fn errno_location() -> *mut c_int {
+    unsafe { libc::__error() }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "emscripten",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+)))]
+fn errno_location() -> *mut c_int {
+    // On other platforms, `errno` is not preserved.
+    std::ptr::null_mut()
+}
+
+/// Install [`handle_signal`] for the given signal, returning the previous signal action.
+fn install_handler(signal: c_int) -> Result<libc::sigaction, std::io::Error> {
+    let index = signal as usize;
+    unsafe {
+        // First, read the current action so that it can be chained once the handler is active.
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        if libc::sigaction(signal, std::ptr::null(), &mut previous) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        PREVIOUS_HANDLER[index].store(previous.sa_sigaction, Ordering::SeqCst);
+        PREVIOUS_SIGINFO[index].store(previous.sa_flags & libc::SA_SIGINFO != 0, Ordering::SeqCst);
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_signal as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(previous)
+    }
+}
+
+/// Restore the signal action that was active before [`install_handler`]. This is only used
+/// if the handlers of a new trigger cannot be installed.
+fn restore_handler(signal: c_int, previous: &libc::sigaction) {
+    if unsafe { libc::sigaction(signal, previous, std::ptr::null_mut()) } != 0 {
+        warn!(
+            "`CancelSignal` cannot restore the original handler of {}: {:?}",
+            signal_name(signal),
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// A human-readable name of the given signal.
+fn signal_name(signal: c_int) -> String {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGWINCH => "SIGWINCH",
+        _ => return format!("signal {signal}"),
+    };
+    name.to_string()
+}
+
+/// Implementation of [`CancellationTrigger`] that is canceled when the process receives one of
+/// the given signals (e.g., `SIGTERM` sent by a service manager, or `SIGHUP`). Once canceled,
+/// the received signal is reported through [`Cancelled::detail`] and [`CancelSignal::signal`].
+///
+/// The signals are observed using a shared signal handler which only notifies a watcher thread
+/// (using a "self-pipe"), and the watcher thread then cancels the waiting triggers. The signal
+/// handler is installed once the first trigger observing the signal is created, and it stays
+/// installed for the lifetime of the process, since restoring the original action could
+/// overwrite handlers installed in the meantime. Consequently, once observed, the signal no
+/// longer performs its default action (e.g., `SIGTERM` does not terminate the process), even
+/// if no trigger is waiting for it.
+///
+/// The trigger coexists with [`crate::CancelCtrlc`] (and other handlers installed before it),
+/// because the previously installed handler is always called as well.
+///
+/// See also [`on_signal`].
+///
+/// ## Logging
+///  - `[trace]` Every time a signal is processed, the number of affected triggers is listed.
+///    Each trigger should also produce a message once actually canceled.
+///  - `[warn]` If the original signal action cannot be restored (after a failed installation),
+///    or the watcher thread fails.
+#[derive(Debug, Clone)]
+pub struct CancelSignal {
+    trigger: CancelAtomic,
+    received: Arc<OnceLock<c_int>>,
+    // The registration is only needed to unregister the trigger once all copies are dropped.
+    #[allow(dead_code)]
+    registration: Arc<Registration>,
+}
+
+impl CancellationTrigger for CancelSignal {
+    fn is_cancelled(&self) -> bool {
+        self.trigger.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelSignal"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.trigger.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.trigger.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.signal().map(signal_name)
+    }
+}
+
+impl CancelSignal {
+    /// Create a new [`CancelSignal`] that is canceled once any of the given `signals`
+    /// is received (use the constants provided by the `libc` crate, e.g., `libc::SIGTERM`).
+    ///
+    /// # Panics
+    /// The operation panics if the signal handler cannot be installed
+    /// (see [`CancelSignal::try_new`]).
+    pub fn new(signals: &[c_int]) -> Self {
+        Self::try_new(signals).unwrap()
+    }
+
+    /// Try to create a new instance of [`CancelSignal`], returning an error if one of the
+    /// `signals` cannot be observed (e.g., `SIGKILL`), or if the signal handler cannot
+    /// be installed.
+    pub fn try_new(signals: &[c_int]) -> Result<Self, std::io::Error> {
+        if let Err(e) = WATCHER_INITIALIZED.as_ref() {
+            return Err(std::io::Error::new(e.kind(), e.to_string()));
+        }
+        for signal in signals {
+            let is_valid = *signal > 0 && (*signal as usize) < SIGNAL_COUNT;
+            if !is_valid || FORBIDDEN_SIGNALS.contains(signal) {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "{} cannot be observed by `CancelSignal`.",
+                        signal_name(*signal)
+                    ),
+                ));
+            }
+        }
+
+        let mut signals = signals.to_vec();
+        signals.sort();
+        signals.dedup();
+
+        let mut state = signal_state();
+        // Install the handler for all signals that are not observed yet. If this fails,
+        // the handlers installed so far are restored.
+        let mut new_handlers = Vec::new();
+        for signal in &signals {
+            if state.installed.contains(signal) {
+                continue;
+            }
+            match install_handler(*signal) {
+                Ok(previous) => new_handlers.push((*signal, previous)),
+                Err(e) => {
+                    for (signal, previous) in &new_handlers {
+                        restore_handler(*signal, previous);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        for (signal, _) in new_handlers {
+            state.installed.push(signal);
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        let trigger = CancelAtomic::default();
+        let received = Arc::new(OnceLock::new());
+        state.waiting.push(SignalWaiter {
+            id,
+            signals,
+            trigger: trigger.clone(),
+            received: received.clone(),
+        });
+        Ok(CancelSignal {
+            trigger,
+            received,
+            registration: Arc::new(Registration::new(&SIGNAL_STATE, move |mut state| {
+                // The trigger is not waiting if it has already been canceled.
+                state.waiting.retain(|it| it.id != id);
+            })),
+        })
+    }
+
+    /// The signal that canceled this trigger, or `None` if the trigger is not canceled.
+    pub fn signal(&self) -> Option<c_int> {
+        self.received.get().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::triggers::wait_for;
+    use crate::{CancelSignal, CancellationTrigger};
+
+    #[test]
+    fn signal_raised() {
+        let usr1 = CancelSignal::new(&[libc::SIGUSR1]);
+        let usr2 = CancelSignal::new(&[libc::SIGUSR2]);
+        let both = CancelSignal::new(&[libc::SIGUSR1, libc::SIGUSR2]);
+
+        unsafe { assert_eq!(libc::raise(libc::SIGUSR2), 0) };
+        wait_for(&usr2);
+        wait_for(&both);
+        assert!(!usr1.is_cancelled());
+        assert_eq!(usr2.signal(), Some(libc::SIGUSR2));
+        assert_eq!(both.detail().as_deref(), Some("SIGUSR2"));
+
+        unsafe { assert_eq!(libc::raise(libc::SIGUSR1), 0) };
+        wait_for(&usr1);
+        assert_eq!(usr1.detail().as_deref(), Some("SIGUSR1"));
+        // The signal that canceled the trigger first is preserved.
+        assert_eq!(both.signal(), Some(libc::SIGUSR2));
+    }
+
+    #[test]
+    fn signal_without_triggers() {
+        // SIGALRM terminates the process by default, and it is not used by other tests.
+        let trigger = CancelSignal::new(&[libc::SIGALRM]);
+        drop(trigger);
+        // The handler stays installed, so the signal is ignored once no trigger is waiting.
+        unsafe { assert_eq!(libc::raise(libc::SIGALRM), 0) };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        let trigger = CancelSignal::new(&[libc::SIGALRM]);
+        unsafe { assert_eq!(libc::raise(libc::SIGALRM), 0) };
+        wait_for(&trigger);
+    }
+
+    #[test]
+    fn signal_forbidden() {
+        assert!(CancelSignal::try_new(&[libc::SIGKILL]).is_err());
+        assert!(CancelSignal::try_new(&[0]).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::triggers::global::cancelled_global;
+use crate::{
+    CancelInterrupt, CancellationTrigger, Cancelled, DynamicCancellationTrigger, TriggerListener,
+    check_with_globals, subscribe_globals, unsubscribe_globals,
+};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// A copy of the thread-local cancellation triggers, as returned by [`crate::active_triggers`].
+///
+/// The snapshot is immutable and cloning it is just a reference count increment, so it can be
+/// freely shared between threads (e.g., to initialize cancellation in worker threads using
+/// [`crate::on_trigger`]), or used directly with [`crate::is_cancelled`] to avoid repeated
+/// access to the thread-local triggers. Process-global triggers (see [`crate::install_global`])
+/// also apply to the snapshot.
+///
+/// ```rust
+/// # use cancel_this::{CancelAtomic, Cancellable, is_cancelled};
+/// let trigger = CancelAtomic::new();
+/// let result: Cancellable<()> = cancel_this::on_atomic(trigger.clone(), || {
+///     let snapshot = cancel_this::active_triggers();
+///     assert_eq!(snapshot.type_names(), &["CancelAtomic"]);
+///
+///     let worker = snapshot.clone();
+///     std::thread::spawn(move || {
+///         cancel_this::on_trigger(worker, || {
+///             trigger.cancel();
+///             is_cancelled!()
+///         })
+///     })
+///     .join()
+///     .unwrap()
+/// });
+/// assert_eq!(result.unwrap_err().cause(), "CancelAtomic");
+/// ```
+#[derive(Clone)]
+pub struct TriggerSnapshot(Arc<SnapshotInner>);
+
+struct SnapshotInner {
+    trigger: DynamicCancellationTrigger,
+    type_names: Vec<&'static str>,
+    /// The interrupt flag of the thread that took the snapshot (see [`crate::interrupt`]).
+    interrupt: Option<CancelInterrupt>,
+    /// Notified once the snapshot could be canceled, such that checks of a snapshot that
+    /// is not canceled are a single atomic load.
+    listener: TriggerListener,
+}
+
+impl TriggerSnapshot {
+    pub(crate) fn new(
+        trigger: DynamicCancellationTrigger,
+        type_names: Vec<&'static str>,
+        interrupt: Option<CancelInterrupt>,
+    ) -> Self {
+        let listener = TriggerListener::default();
+        if !trigger.subscribe(&listener) {
+            listener.notify();
+        }
+        if let Some(interrupt) = &interrupt {
+            interrupt.subscribe(&listener);
+        }
+        subscribe_globals(&listener);
+        TriggerSnapshot(Arc::new(SnapshotInner {
+            trigger,
+            type_names,
+            interrupt,
+            listener,
+        }))
+    }
+
+    /// The number of triggers in the snapshot (not counting global triggers).
+    ///
+    /// Note that the interrupt flag of the thread that took the snapshot
+    /// (see [`crate::interrupt`]) is not counted (nor listed in [`TriggerSnapshot::type_names`]),
+    /// but it still applies to the snapshot (unless the snapshot was taken inside
+    /// [`crate::never`]).
+    pub fn len(&self) -> usize {
+        self.0.type_names.len()
+    }
+
+    /// True if the snapshot contains no triggers (not counting global triggers).
+    pub fn is_empty(&self) -> bool {
+        self.0.type_names.is_empty()
+    }
+
+    /// Type names of all triggers in the snapshot, starting with the outermost one.
+    pub fn type_names(&self) -> &[&'static str] {
+        self.0.type_names.as_slice()
+    }
+}
+
+impl Debug for TriggerSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TriggerSnapshot")
+            .field(&self.0.type_names)
+            .finish()
+    }
+}
+
+impl Drop for SnapshotInner {
+    fn drop(&mut self) {
+        self.trigger.unsubscribe(&self.listener);
+        if let Some(interrupt) = &self.interrupt {
+            interrupt.unsubscribe(&self.listener);
+        }
+        unsubscribe_globals(&self.listener);
+    }
+}
+
+impl SnapshotInner {
+    /// The first canceled trigger (or interrupt flag) of the snapshot, not counting
+    /// global triggers.
+    fn cancelled_local(&self) -> Option<&dyn CancellationTrigger> {
+        if self.trigger.is_cancelled() {
+            return Some(self.trigger.as_ref());
+        }
+        self.interrupt
+            .as_ref()
+            .filter(|it| it.is_cancelled())
+            .map(|it| it as &dyn CancellationTrigger)
+    }
+}
+
+impl CancellationTrigger for TriggerSnapshot {
+    fn is_cancelled(&self) -> bool {
+        if !self.0.listener.is_notified() {
+            return false;
+        }
+        self.0.cancelled_local().is_some() || cancelled_global().is_some()
+    }
+
+    fn type_name(&self) -> &'static str {
+        if let Some(trigger) = self.0.cancelled_local() {
+            return trigger.type_name();
+        }
+        cancelled_global()
+            .map(|it| it.type_name())
+            .unwrap_or_else(|| self.0.trigger.type_name())
+    }
+
+    fn detail(&self) -> Option<String> {
+        if let Some(trigger) = self.0.cancelled_local() {
+            return trigger.detail();
+        }
+        match cancelled_global() {
+            Some(global) => global.detail(),
+            None => self.0.trigger.detail(),
+        }
+    }
+
+    fn check(&self) -> Option<Cancelled> {
+        let inner = &self.0;
+        if !inner.listener.is_notified() {
+            return None;
+        }
+        // Global triggers are skipped if the snapshot is checked as a part of the thread-local
+        // triggers, since those are checked together with global triggers.
+        check_with_globals(|| {
+            inner
+                .trigger
+                .check()
+                .or_else(|| inner.interrupt.as_ref().and_then(|it| it.check()))
+        })
+    }
+
+    // Global triggers are not subscribed, since they are checked separately.
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        let is_subscribed = self.0.trigger.subscribe(listener);
+        match &self.0.interrupt {
+            Some(interrupt) => interrupt.subscribe(listener) && is_subscribed,
+            None => is_subscribed,
+        }
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.trigger.unsubscribe(listener);
+        if let Some(interrupt) = &self.0.interrupt {
+            interrupt.unsubscribe(listener);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerSnapshot, is_cancelled};
+
+    #[test]
+    fn snapshot_sharing() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<TriggerSnapshot>();
+
+        let never = crate::never(|| Ok::<_, Cancelled>(crate::active_triggers())).unwrap();
+        assert_eq!(never.type_names(), &["CancelNever"]);
+        assert_eq!(never.type_name(), "CancelNever");
+
+        let trigger = CancelAtomic::new();
+        let snapshot = crate::on_atomic(trigger.clone(), || {
+            Ok::<_, Cancelled>(crate::active_triggers())
+        })
+        .unwrap();
+        assert!(!snapshot.is_empty());
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot.type_names(), &["CancelAtomic"]);
+        // The interrupt flag is not a part of the chain, so a single trigger is not wrapped.
+        assert_eq!(snapshot.0.trigger.type_name(), "CancelAtomic");
+
+        // The interrupt flag is not reported, but it still applies.
+        let idle = crate::active_triggers();
+        assert!(idle.is_empty());
+        assert!(idle.type_names().is_empty());
+        crate::interrupt(std::thread::current().id());
+        assert_eq!(idle.check().unwrap().cause(), crate::INTERRUPTED_CAUSE);
+        crate::clear_interrupt();
+
+        // Clones share the same triggers, even once the scope is exited.
+        let copy = snapshot.clone();
+        assert!(is_cancelled!(copy).is_ok());
+        trigger.cancel();
+        assert_eq!(is_cancelled!(copy).unwrap_err().cause(), "CancelAtomic");
+        assert!(snapshot.is_cancelled());
+    }
+}
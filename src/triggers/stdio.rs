@@ -0,0 +1,523 @@
+use crate::registration::Registration;
+use crate::triggers::watcher::{Watch, WatchTrigger, register_watch};
+use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerListener};
+use log::{trace, warn};
+use std::os::fd::RawFd;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Run the given `action`, cancelling it using [`CancelStdinClosed`] once the standard input
+/// of this process is closed (e.g., the controlling terminal or the writing process exits).
+///
+/// # Panics
+/// The operation panics if the shared watcher thread cannot be started
+/// (see [`CancelStdinClosed::try_new`]).
+pub fn on_stdin_closed<TResult, TError, TAction>(action: TAction) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(CancelStdinClosed::new(), action)
+}
+
+/// Run the given `action`, cancelling it using [`CancelStdoutBroken`] once the standard output
+/// of this process is a broken pipe (e.g., the output is piped into `head`, which already
+/// exited).
+///
+/// # Panics
+/// The operation panics if the shared watcher thread cannot be started
+/// (see [`CancelStdoutBroken::try_new`]).
+pub fn on_stdout_broken<TResult, TError, TAction>(action: TAction) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(CancelStdoutBroken::new(), action)
+}
+
+/// Run the given `action`, cancelling it using [`CancelKey`] once the given `key` is pressed
+/// in the terminal connected to the standard input.
+///
+/// # Panics
+/// The operation panics if the shared watcher thread cannot be started
+/// (see [`CancelKey::try_new`]).
+pub fn on_key<TResult, TError, TAction>(key: char, action: TAction) -> Result<TResult, TError>
+where
+    TAction: FnOnce() -> Result<TResult, TError>,
+    TError: From<Cancelled>,
+{
+    crate::on_trigger(CancelKey::new(key), action)
+}
+
+/// Implementation of [`CancellationTrigger`] that is canceled once the standard input
+/// of this process is closed, i.e., it reports a "hang up" (`POLLHUP`). This happens when
+/// all writers of a pipe exit, or when the controlling terminal is closed. Standard input that
+/// is a regular file (or `/dev/null`) never cancels this trigger.
+///
+/// The standard input is observed by a shared watcher thread without reading any data from it,
+/// meaning cancellation checks are as cheap as [`CancelAtomic`].
+///
+/// See also [`on_stdin_closed`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled.
+#[derive(Debug, Clone)]
+pub struct CancelStdinClosed(WatchTrigger);
+
+impl CancellationTrigger for CancelStdinClosed {
+    fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelStdinClosed"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.0.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.0.detail()
+    }
+}
+
+impl Default for CancelStdinClosed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelStdinClosed {
+    /// Create a new [`CancelStdinClosed`] trigger.
+    ///
+    /// # Panics
+    /// The operation panics if the shared watcher thread cannot be started
+    /// (see [`CancelStdinClosed::try_new`]).
+    pub fn new() -> Self {
+        Self::try_new().unwrap()
+    }
+
+    /// Try to create a new [`CancelStdinClosed`], returning an error if the shared watcher
+    /// thread cannot be started.
+    pub fn try_new() -> Result<Self, std::io::Error> {
+        Self::watch_fd(libc::STDIN_FILENO)
+    }
+
+    fn watch_fd(fd: RawFd) -> Result<Self, std::io::Error> {
+        let watch = watch_hang_up("CancelStdinClosed", fd, "stdin closed")?;
+        Ok(CancelStdinClosed(watch))
+    }
+}
+
+/// Implementation of [`CancellationTrigger`] that is canceled once the standard output
+/// of this process is broken, i.e., it reports an error or a "hang up" (`POLLERR`/`POLLHUP`).
+/// This happens when the output is piped into a process which exits before reading
+/// everything (e.g., `head`).
+///
+/// The standard output is observed by a shared watcher thread without writing any data
+/// into it, meaning cancellation checks are as cheap as [`CancelAtomic`]. It is thus possible
+/// to stop the computation even before the process tries to write its output.
+///
+/// See also [`on_stdout_broken`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled.
+#[derive(Debug, Clone)]
+pub struct CancelStdoutBroken(WatchTrigger);
+
+impl CancellationTrigger for CancelStdoutBroken {
+    fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelStdoutBroken"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.0.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.0.detail()
+    }
+}
+
+impl Default for CancelStdoutBroken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelStdoutBroken {
+    /// Create a new [`CancelStdoutBroken`] trigger.
+    ///
+    /// # Panics
+    /// The operation panics if the shared watcher thread cannot be started
+    /// (see [`CancelStdoutBroken::try_new`]).
+    pub fn new() -> Self {
+        Self::try_new().unwrap()
+    }
+
+    /// Try to create a new [`CancelStdoutBroken`], returning an error if the shared watcher
+    /// thread cannot be started.
+    pub fn try_new() -> Result<Self, std::io::Error> {
+        Self::watch_fd(libc::STDOUT_FILENO)
+    }
+
+    fn watch_fd(fd: RawFd) -> Result<Self, std::io::Error> {
+        let watch = watch_hang_up("CancelStdoutBroken", fd, "stdout broken")?;
+        Ok(CancelStdoutBroken(watch))
+    }
+}
+
+/// Register a watch which is canceled once the given descriptor reports an error
+/// or a "hang up". Since no events are requested, only these conditions are reported
+/// by `poll` and the descriptor is never read or written.
+fn watch_hang_up(
+    name: &'static str,
+    fd: RawFd,
+    detail: &'static str,
+) -> Result<WatchTrigger, std::io::Error> {
+    register_watch(
+        name,
+        Watch {
+            fd: Some((fd, 0)),
+            interval: None,
+            probe: Box::new(move |revents| {
+                let is_broken = revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0;
+                is_broken.then(|| detail.to_string())
+            }),
+        },
+    )
+}
+
+/// Implementation of [`CancellationTrigger`] that is canceled once the given key is pressed
+/// in the terminal connected to the standard input (e.g., `q` for "quit").
+///
+/// While at least one [`CancelKey`] exists, the terminal is switched to non-canonical mode
+/// without echo (i.e., key presses are delivered immediately and are not printed).
+/// Once the last waiting trigger is dropped, the original terminal mode is restored. All triggers
+/// share a single reader of the standard input on the shared watcher thread, and everything
+/// else the user types is discarded. If the standard input is not a terminal, the trigger
+/// is never canceled.
+///
+/// See also [`on_key`].
+///
+/// ## Logging
+///  - `[trace]` Every time the trigger is canceled.
+///  - `[warn]` If the standard input is not a terminal, or the terminal mode cannot be
+///    changed or restored.
+#[derive(Debug, Clone)]
+pub struct CancelKey {
+    key: char,
+    trigger: CancelAtomic,
+    // The registration is only needed to unregister the trigger once all copies are dropped.
+    #[allow(dead_code)]
+    registration: Arc<Registration>,
+}
+
+impl CancellationTrigger for CancelKey {
+    fn is_cancelled(&self) -> bool {
+        self.trigger.is_cancelled()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "CancelKey"
+    }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.trigger.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.trigger.unsubscribe(listener)
+    }
+
+    fn detail(&self) -> Option<String> {
+        self.is_cancelled()
+            .then(|| format!("key '{}' pressed", self.key))
+    }
+
+    fn check(&self) -> Option<Cancelled> {
+        self.is_cancelled()
+            .then(|| Cancelled::with_detail("CancelKey", format!("key '{}' pressed", self.key)))
+    }
+}
+
+impl CancelKey {
+    /// Create a new [`CancelKey`] which is canceled once `key` is pressed.
+    ///
+    /// # Panics
+    /// The operation panics if the shared watcher thread cannot be started
+    /// (see [`CancelKey::try_new`]).
+    pub fn new(key: char) -> Self {
+        Self::try_new(key).unwrap()
+    }
+
+    /// Try to create a new [`CancelKey`], returning an error if the shared watcher
+    /// thread cannot be started.
+    pub fn try_new(key: char) -> Result<Self, std::io::Error> {
+        Self::watch_fd(key, libc::STDIN_FILENO, true)
+    }
+
+    /// The key which cancels this trigger.
+    pub fn key(&self) -> char {
+        self.key
+    }
+
+    /// Create the trigger, starting a shared reader of `fd` if there is none. If
+    /// `require_terminal` is set and `fd` is not a terminal, the reader is not started.
+    fn watch_fd(key: char, fd: RawFd, require_terminal: bool) -> Result<Self, std::io::Error> {
+        // Lock order: key reader, watcher state, key waiters.
+        let mut reader = key_reader();
+        if reader.is_none() {
+            *reader = Some(KeyReader::start(fd, require_terminal)?);
+        }
+        let trigger = CancelAtomic::default();
+        let mut waiters = key_waiters();
+        let id = waiters.next_id;
+        waiters.next_id += 1;
+        waiters.waiting.push((id, key, trigger.clone()));
+        Ok(CancelKey {
+            key,
+            trigger,
+            registration: Arc::new(Registration::new(&KEY_READER, move |mut reader| {
+                let Ok(mut waiters) = KEY_WAITERS.lock() else {
+                    return;
+                };
+                // The trigger is not waiting if it has already been canceled.
+                waiters.waiting.retain(|(it, _, _)| *it != id);
+                let is_last = waiters.waiting.is_empty();
+                drop(waiters);
+                if is_last {
+                    // Stops the reader and restores the terminal mode (the watcher lock is
+                    // acquired here, so the waiters lock must be released first).
+                    reader.take();
+                }
+            })),
+        })
+    }
+}
+
+/// Private global list of [`CancelKey`] triggers waiting for a key press. No other lock
+/// is acquired while holding this lock.
+static KEY_WAITERS: Mutex<KeyWaiters> = Mutex::new(KeyWaiters {
+    next_id: 0,
+    waiting: Vec::new(),
+});
+
+/// Private global reader of the standard input, active while at least one [`CancelKey`]
+/// exists.
+static KEY_READER: Mutex<Option<KeyReader>> = Mutex::new(None);
+
+struct KeyWaiters {
+    next_id: u64,
+    waiting: Vec<(u64, char, CancelAtomic)>,
+}
+
+fn key_waiters() -> MutexGuard<'static, KeyWaiters> {
+    KEY_WAITERS
+        .lock()
+        .expect("Global state of `CancelKey` is corrupted.")
+}
+
+fn key_reader() -> MutexGuard<'static, Option<KeyReader>> {
+    KEY_READER
+        .lock()
+        .expect("Global state of `CancelKey` is corrupted.")
+}
+
+/// Cancel all waiting triggers whose key appears in the given `input`.
+fn dispatch_keys(input: &[u8]) {
+    let mut waiters = key_waiters();
+    waiters.waiting.retain(|(_, key, trigger)| {
+        let mut encoded = [0u8; 4];
+        let encoded = key.encode_utf8(&mut encoded).as_bytes();
+        if input.windows(encoded.len()).any(|it| it == encoded) {
+            trace!(
+                "Key '{key}' pressed. Cancelling `CancelKey[{:p}]`.",
+                trigger.id_ref()
+            );
+            trigger.cancel();
+            false
+        } else {
+            true
+        }
+    });
+}
+
+/// Reads the key presses from the given descriptor on the shared watcher thread. If the
+/// descriptor is a terminal, its mode is changed to non-canonical without echo until
+/// the reader is dropped.
+struct KeyReader {
+    fd: RawFd,
+    original_mode: Option<libc::termios>,
+    // The watch is never accessed. It only needs to be dropped together with the reader.
+    #[allow(dead_code)]
+    watch: Option<WatchTrigger>,
+}
+
+impl KeyReader {
+    fn start(fd: RawFd, require_terminal: bool) -> Result<Self, std::io::Error> {
+        let is_terminal = unsafe { libc::isatty(fd) } == 1;
+        if !is_terminal && require_terminal {
+            warn!("Standard input is not a terminal. `CancelKey` will never be canceled.");
+            return Ok(KeyReader {
+                fd,
+                original_mode: None,
+                watch: None,
+            });
+        }
+        let original_mode = if is_terminal { set_raw_mode(fd) } else { None };
+        let watch = register_watch(
+            "CancelKey",
+            Watch {
+                fd: Some((fd, libc::POLLIN)),
+                interval: None,
+                probe: Box::new(move |_| {
+                    let mut buffer = [0u8; 64];
+                    let read = unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+                    if read > 0 {
+                        dispatch_keys(&buffer[..read as usize]);
+                    }
+                    // The reader itself is never canceled.
+                    None
+                }),
+            },
+        );
+        match watch {
+            Ok(watch) => Ok(KeyReader {
+                fd,
+                original_mode,
+                watch: Some(watch),
+            }),
+            Err(e) => {
+                if let Some(original_mode) = original_mode {
+                    restore_mode(fd, &original_mode);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Drop for KeyReader {
+    fn drop(&mut self) {
+        if let Some(original_mode) = self.original_mode.as_ref() {
+            restore_mode(self.fd, original_mode);
+        }
+    }
+}
+
+/// Switch the terminal to non-canonical mode without echo, returning the original mode.
+fn set_raw_mode(fd: RawFd) -> Option<libc::termios> {
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        warn!(
+            "`CancelKey` cannot read the terminal mode: {:?}",
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    raw.c_cc[libc::VMIN] = 1;
+    raw.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        warn!(
+            "`CancelKey` cannot change the terminal mode: {:?}",
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    Some(original)
+}
+
+fn restore_mode(fd: RawFd, original: &libc::termios) {
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, original) } != 0 {
+        warn!(
+            "`CancelKey` cannot restore the terminal mode: {:?}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::triggers::wait_for;
+    use crate::{CancelKey, CancelStdinClosed, CancelStdoutBroken, CancellationTrigger};
+    use std::time::Duration;
+
+    /// Create a new pipe, returning the read and write ends.
+    fn pipe() -> (libc::c_int, libc::c_int) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        (fds[0], fds[1])
+    }
+
+    #[test]
+    fn stdin_closed() {
+        let (read, write) = pipe();
+        let trigger = CancelStdinClosed::watch_fd(read).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!trigger.is_cancelled());
+
+        unsafe { libc::close(write) };
+        wait_for(&trigger);
+        assert_eq!(trigger.detail(), Some("stdin closed".to_string()));
+        drop(trigger);
+        unsafe { libc::close(read) };
+    }
+
+    #[test]
+    fn stdout_broken() {
+        let (read, write) = pipe();
+        let trigger = CancelStdoutBroken::watch_fd(write).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!trigger.is_cancelled());
+
+        unsafe { libc::close(read) };
+        wait_for(&trigger);
+        assert_eq!(trigger.detail(), Some("stdout broken".to_string()));
+        drop(trigger);
+        unsafe { libc::close(write) };
+    }
+
+    #[test]
+    fn key_pressed() {
+        let (read, write) = pipe();
+        let quit = CancelKey::watch_fd('q', read, false).unwrap();
+        let stop = CancelKey::watch_fd('ß', read, false).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        let input = "xyq".as_bytes();
+        assert_eq!(
+            unsafe { libc::write(write, input.as_ptr().cast(), input.len()) },
+            3
+        );
+        wait_for(&quit);
+        assert_eq!(quit.detail(), Some("key 'q' pressed".to_string()));
+        assert!(!stop.is_cancelled());
+
+        let input = "ß".as_bytes();
+        assert_eq!(
+            unsafe { libc::write(write, input.as_ptr().cast(), input.len()) },
+            2
+        );
+        wait_for(&stop);
+        assert_eq!(stop.key(), 'ß');
+
+        drop((quit, stop));
+        unsafe { libc::close(write) };
+        unsafe { libc::close(read) };
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{CancelAtomic, CancellationTrigger, Cancelled};
+use crate::{CancelAtomic, CancellationTrigger, Cancelled, TriggerListener};
 use log::{trace, warn};
 use std::sync::Arc;
 use std::sync::mpsc::Sender;
@@ -59,6 +59,14 @@ impl CancellationTrigger for CancelTimer {
     fn type_name(&self) -> &'static str {
         "CancelTimer"
     }
+
+    fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.0.subscribe(listener)
+    }
+
+    fn unsubscribe(&self, listener: &TriggerListener) {
+        self.0.unsubscribe(listener)
+    }
 }
 
 impl CancelTimer {
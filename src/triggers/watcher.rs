@@ -0,0 +1,266 @@
+use crate::registration::Registration;
+use crate::{CancelAtomic, CancellationTrigger, TriggerListener};
+use lazy_static::lazy_static;
+use libc::{c_short, pollfd};
+use log::{trace, warn};
+use std::io::{ErrorKind, Read};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Describes what the watcher thread should observe for a single trigger.
+pub(crate) struct Watch {
+    /// A file descriptor that is polled for the given events. The descriptor must stay valid
+    /// while the watch is registered (typically, it is owned by the `probe` closure).
+    pub fd: Option<(RawFd, c_short)>,
+    /// If set, the probe is also executed periodically with this interval.
+    pub interval: Option<Duration>,
+    /// Called with the returned events of `fd` (or `0` if called periodically). Returns
+    /// a human-readable detail once the trigger should be canceled. If the descriptor stays
+    /// ready, the probe must either consume the event or cancel the trigger, otherwise
+    /// the watcher thread would keep waking up. If the descriptor reports a permanent
+    /// condition (`POLLHUP`, `POLLERR` or `POLLNVAL`) and the probe does not cancel the trigger,
+    /// the descriptor is no longer observed.
+    pub probe: Box<dyn FnMut(c_short) -> Option<String> + Send>,
+}
+
+/// A cancellation flag controlled by the watcher thread. The registered [`Watch`]
+/// is removed once all copies of this value are dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct WatchTrigger {
+    trigger: CancelAtomic,
+    detail: Arc<OnceLock<String>>,
+    // The registration is only needed to unregister the watch once all copies are dropped.
+    #[allow(dead_code)]
+    registration: Arc<Registration>,
+}
+
+impl WatchTrigger {
+    pub fn is_cancelled(&self) -> bool {
+        self.trigger.is_cancelled()
+    }
+
+    pub fn detail(&self) -> Option<String> {
+        self.detail.get().cloned()
+    }
+
+    pub fn subscribe(&self, listener: &TriggerListener) -> bool {
+        self.trigger.subscribe(listener)
+    }
+
+    pub fn unsubscribe(&self, listener: &TriggerListener) {
+        self.trigger.unsubscribe(listener)
+    }
+
+    /// Cancel the trigger directly (e.g., when the watched event is detected
+    /// while the watch is being created).
+    #[cfg(any(feature = "process", feature = "file"))]
+    pub fn cancel(&self, detail: String) {
+        let _ = self.detail.set(detail);
+        self.trigger.cancel();
+    }
+}
+
+struct WatchEntry {
+    id: u64,
+    name: &'static str,
+    watch: Watch,
+    next_probe: Option<Instant>,
+    trigger: CancelAtomic,
+    detail: Arc<OnceLock<String>>,
+}
+
+struct WatcherState {
+    next_id: u64,
+    entries: Vec<WatchEntry>,
+}
+
+/// Private global state of the watcher thread.
+static WATCHER_STATE: Mutex<WatcherState> = Mutex::new(WatcherState {
+    next_id: 0,
+    entries: Vec::new(),
+});
+
+lazy_static! {
+    /// The write end of the "wake-up" pipe of the watcher thread, created exactly once before
+    /// the first watch is registered.
+    static ref WATCHER_WAKE: Result<UnixStream, std::io::Error> = start_watcher();
+}
+
+fn watcher_state() -> MutexGuard<'static, WatcherState> {
+    WATCHER_STATE
+        .lock()
+        .expect("Global state of the trigger watcher thread is corrupted.")
+}
+
+/// Register a new [`Watch`] for a trigger with the given type `name` (used for logging).
+pub(crate) fn register_watch(
+    name: &'static str,
+    watch: Watch,
+) -> Result<WatchTrigger, std::io::Error> {
+    if let Err(e) = WATCHER_WAKE.as_ref() {
+        return Err(std::io::Error::new(e.kind(), e.to_string()));
+    }
+    let trigger = CancelAtomic::default();
+    let detail = Arc::new(OnceLock::new());
+    let mut state = watcher_state();
+    let id = state.next_id;
+    state.next_id += 1;
+    let next_probe = watch.interval.map(|it| Instant::now() + it);
+    state.entries.push(WatchEntry {
+        id,
+        name,
+        watch,
+        next_probe,
+        trigger: trigger.clone(),
+        detail: detail.clone(),
+    });
+    drop(state);
+    wake_watcher();
+    Ok(WatchTrigger {
+        trigger,
+        detail,
+        registration: Arc::new(Registration::new(&WATCHER_STATE, move |mut state| {
+            let removed = state
+                .entries
+                .iter()
+                .position(|it| it.id == id)
+                .map(|index| state.entries.swap_remove(index));
+            drop(state);
+            if removed.is_some() {
+                wake_watcher();
+            }
+            // The watch (including any owned file descriptor) is dropped here, outside the lock.
+            drop(removed);
+        })),
+    })
+}
+
+/// Notify the watcher thread that the set of watches changed.
+fn wake_watcher() {
+    if let Ok(mut stream) = WATCHER_WAKE.as_ref() {
+        // If the pipe is full, the watcher is going to wake up anyway.
+        let _ = std::io::Write::write(&mut stream, &[0u8]);
+    }
+}
+
+/// Start the watcher thread. Once started, the thread runs until the application is terminated.
+fn start_watcher() -> Result<UnixStream, std::io::Error> {
+    let (mut reader, writer) = UnixStream::pair()?;
+    reader.set_nonblocking(true)?;
+    writer.set_nonblocking(true)?;
+    std::thread::Builder::new()
+        .name("cancel-this-watcher".to_string())
+        .spawn(move || {
+            loop {
+                if let Err(e) = watch_once(&mut reader) {
+                    warn!("Trigger watcher thread failed: {e:?}");
+                    return;
+                }
+            }
+        })?;
+    Ok(writer)
+}
+
+/// Wait for the next event (or timeout) and run the affected probes.
+fn watch_once(reader: &mut UnixStream) -> Result<(), std::io::Error> {
+    // Collect the descriptors and the earliest deadline while holding the lock.
+    let mut fds = vec![pollfd {
+        fd: reader.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    }];
+    let mut ids = Vec::new();
+    let mut deadline: Option<Instant> = None;
+    {
+        let state = watcher_state();
+        for entry in &state.entries {
+            if let Some((fd, events)) = entry.watch.fd {
+                fds.push(pollfd {
+                    fd,
+                    events,
+                    revents: 0,
+                });
+                ids.push(entry.id);
+            }
+            if let Some(next) = entry.next_probe {
+                deadline = Some(deadline.map_or(next, |it| it.min(next)));
+            }
+        }
+    }
+
+    let timeout = match deadline {
+        None => -1,
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            poll_timeout(remaining)
+        }
+    };
+
+    let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+    if result < 0 {
+        let error = std::io::Error::last_os_error();
+        return if error.kind() == ErrorKind::Interrupted {
+            Ok(())
+        } else {
+            Err(error)
+        };
+    }
+
+    if fds[0].revents != 0 {
+        // Drain the wake-up pipe. The notifications themselves carry no information.
+        let mut buffer = [0u8; 64];
+        while let Ok(read) = reader.read(&mut buffer) {
+            if read == 0 {
+                return Err(std::io::Error::new(
+                    ErrorKind::BrokenPipe,
+                    "Wake-up pipe closed.",
+                ));
+            }
+        }
+    }
+
+    let now = Instant::now();
+    let mut state = watcher_state();
+    let mut fired = Vec::new();
+    for entry in state.entries.iter_mut() {
+        let revents = ids
+            .iter()
+            .position(|it| *it == entry.id)
+            .map(|index| fds[index + 1].revents)
+            .unwrap_or(0);
+        let is_due = entry.next_probe.is_some_and(|it| it <= now);
+        if revents == 0 && !is_due {
+            continue;
+        }
+        if is_due && let Some(interval) = entry.watch.interval {
+            entry.next_probe = Some(now + interval);
+        }
+        if let Some(detail) = (entry.watch.probe)(revents) {
+            trace!(
+                "`{}[{:p}]` canceled ({}).",
+                entry.name,
+                entry.trigger.id_ref(),
+                detail
+            );
+            let _ = entry.detail.set(detail);
+            entry.trigger.cancel();
+            fired.push(entry.id);
+        } else if revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0 {
+            // These conditions are permanent, so the descriptor would stay "ready" forever.
+            // Since the probe decided not to cancel the trigger, we stop observing it.
+            entry.watch.fd = None;
+        }
+    }
+    // Once canceled, the triggers no longer need to be observed.
+    state.entries.retain(|entry| !fired.contains(&entry.id));
+    Ok(())
+}
+
+/// Convert a duration to a `poll` timeout, rounding up to whole milliseconds (so that
+/// the watcher does not wake up just before the deadline).
+fn poll_timeout(duration: Duration) -> libc::c_int {
+    let millis = duration.as_nanos().div_ceil(1_000_000);
+    libc::c_int::try_from(millis).unwrap_or(libc::c_int::MAX)
+}